@@ -1,8 +1,9 @@
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{
-    parse_macro_input, parse_quote, visit_mut::VisitMut, Attribute, FnArg, Ident, ItemFn, ItemMod,
-    PatType, ReturnType, Type,
+    parse_macro_input, parse_quote, punctuated::Punctuated, visit_mut::VisitMut, Attribute, Data,
+    DeriveInput, Expr, Fields, FnArg, Ident, ItemFn, ItemMod, Lit, MetaNameValue, PatType,
+    ReturnType, Token, Type,
 };
 
 #[derive(Clone)]
@@ -13,8 +14,76 @@ struct ApiEntry {
     return_type: Box<Type>,
 }
 
+struct MigrationEntry {
+    func: Ident,
+    from: u32,
+    to: u32,
+}
+
 struct ApiVisitor {
     entries: Vec<ApiEntry>,
+    migrations: Vec<MigrationEntry>,
+    entities: Vec<Ident>,
+}
+
+/// The typed storage containers which implement `StorageLayout`.
+const STORAGE_TYPES: &[&str] = &[
+    "StorageMap",
+    "SealedMap",
+    "Queue",
+    "Deque",
+    "SortedSet",
+    "MerkleLog",
+    "EventStore",
+];
+
+fn is_storage_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| STORAGE_TYPES.iter().any(|t| s.ident == t)),
+        _ => false,
+    }
+}
+
+/// Whether a function returning `ty` fails with the `Err` of a `Result` or `CallResult`.
+fn is_fallible(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == "Result" || s.ident == "CallResult"),
+        _ => false,
+    }
+}
+
+/// The storage containers declared as a `const` of the module.
+fn storage_consts(items: &[syn::Item]) -> Vec<Ident> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Const(c) if is_storage_type(&c.ty) => Some(c.ident.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn derives(attrs: &[Attribute], name: &str) -> bool {
+    attrs
+        .iter()
+        .filter(|a| a.path().is_ident("derive"))
+        .any(|a| {
+            a.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+                .map(|paths| {
+                    paths
+                        .iter()
+                        .any(|p| p.segments.last().is_some_and(|s| s.ident == name))
+                })
+                .unwrap_or(false)
+        })
 }
 
 fn find_entry(attrs: &[Attribute]) -> Option<&str> {
@@ -28,8 +97,56 @@ fn find_entry(attrs: &[Attribute]) -> Option<&str> {
     None
 }
 
+type MigrationArgs = Punctuated<MetaNameValue, Token![,]>;
+
+fn migration_versions(args: MigrationArgs) -> (u32, u32) {
+    let (mut from, mut to) = (None, None);
+    for arg in args {
+        let version = match arg.value {
+            Expr::Lit(syn::ExprLit {
+                lit: Lit::Int(ref v),
+                ..
+            }) => v
+                .base10_parse::<u32>()
+                .expect("migration version should be u32"),
+            _ => panic!("migration version should be an integer literal"),
+        };
+        if arg.path.is_ident("from") {
+            from = Some(version);
+        } else if arg.path.is_ident("to") {
+            to = Some(version);
+        } else {
+            panic!("unknown migration argument, expected `from` or `to`");
+        }
+    }
+    let from = from.expect("migration should specify `from`");
+    let to = to.expect("migration should specify `to`");
+    if to <= from {
+        panic!("migration should upgrade to a greater version");
+    }
+    (from, to)
+}
+
 impl VisitMut for ApiVisitor {
+    fn visit_item_struct_mut(&mut self, item: &mut syn::ItemStruct) {
+        if derives(&item.attrs, "Entity") {
+            self.entities.push(item.ident.clone());
+        }
+        syn::visit_mut::visit_item_struct_mut(self, item);
+    }
+
     fn visit_item_fn_mut(&mut self, item: &mut syn::ItemFn) {
+        if let Some(attr) = item.attrs.iter().find(|a| a.path().is_ident("migration")) {
+            let args = attr
+                .parse_args_with(MigrationArgs::parse_terminated)
+                .expect("expected `#[migration(from = <version>, to = <version>)]`");
+            let (from, to) = migration_versions(args);
+            self.migrations.push(MigrationEntry {
+                func: item.sig.ident.clone(),
+                from,
+                to,
+            });
+        }
         if let Some(method) = find_entry(&item.attrs) {
             let name = item.sig.ident.to_string();
             let param_types: Vec<_> = item
@@ -57,10 +174,14 @@ impl VisitMut for ApiVisitor {
 }
 
 #[proc_macro_attribute]
-pub fn nucleus(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn nucleus(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr with NucleusArgs::parse_terminated);
+    let batch = nucleus_batch(args);
     let mut input_mod = parse_macro_input!(item as ItemMod);
     let mut visitor = ApiVisitor {
         entries: Vec::new(),
+        migrations: Vec::new(),
+        entities: Vec::new(),
     };
     visitor.visit_item_mod_mut(&mut input_mod);
     let entries: Vec<_> = visitor
@@ -85,12 +206,83 @@ pub fn nucleus(_attr: TokenStream, item: TokenStream) -> TokenStream {
             }
         })
         .collect::<Vec<_>>();
+    // an entity is registered as the storage of its repository, which registers its type
+    let entities = visitor.entities.iter().map(|entity| {
+        let name = entity.to_string();
+        let repository = format_ident!("{}Repository", entity);
+        quote! {
+            ::vrs_core_sdk::abi::StorageLayout::register_storage(
+                &#repository::STORE,
+                #name,
+                &mut registry,
+            );
+        }
+    });
+    let storage = input_mod
+        .content
+        .as_ref()
+        .map(|(_, items)| storage_consts(items))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|ident| {
+            let name = ident.to_string();
+            quote! {
+                ::vrs_core_sdk::abi::StorageLayout::register_storage(&#ident, #name, &mut registry);
+            }
+        });
+    let migrations = visitor
+        .migrations
+        .iter()
+        .map(|m| {
+            let func = &m.func;
+            let from = m.from;
+            let to = m.to;
+            parse_quote! {
+                ::vrs_core_sdk::storage::migration::Migration {
+                    from: #from,
+                    to: #to,
+                    run: #func,
+                }
+            }
+        })
+        .collect::<Vec<Expr>>();
+    let batch_entries = visitor
+        .entries
+        .iter()
+        .filter(|entry| entry.method == "post")
+        .map(|entry| {
+            let name = &entry.name;
+            let call = format_ident!("__vrs_dispatch__nucleus_post_{}", name);
+            quote! {
+                ::vrs_core_sdk::batch::Entry {
+                    name: #name,
+                    call: #call,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let batch_api = batch.then(|| {
+        quote! {
+            registry.register_api(
+                "_batch".to_string(),
+                "post".to_string(),
+                vec![::vrs_core_sdk::scale_info::meta_type::<Vec<::vrs_core_sdk::batch::Call>>()],
+                ::vrs_core_sdk::scale_info::meta_type::<Result<Vec<::vrs_core_sdk::batch::Outcome>, String>>(),
+            );
+        }
+    });
     if let Some((_, ref mut items)) = input_mod.content {
+        if batch {
+            push_batch(items, batch_entries);
+        }
         items.push(parse_quote! {
             vrs_core_sdk::lazy_static::lazy_static! {
                 static ref TYPES: ::vrs_core_sdk::abi::ApiRegistry = {
                     let mut registry = ::vrs_core_sdk::abi::ApiRegistry::new();
                     #(#entries)*
+                    #(#entities)*
+                    #(#storage)*
+                    #batch_api
                     registry
                 };
             }
@@ -111,6 +303,9 @@ pub fn nucleus(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 ptr
             }
         });
+        if !migrations.is_empty() {
+            push_migrations(items, migrations);
+        }
     }
     quote! {
         #input_mod
@@ -118,11 +313,140 @@ pub fn nucleus(_attr: TokenStream, item: TokenStream) -> TokenStream {
     .into()
 }
 
+fn push_batch(items: &mut Vec<syn::Item>, entries: Vec<proc_macro2::TokenStream>) {
+    items.push(parse_quote! {
+        #[doc(hidden)]
+        pub const __VRS_BATCH_ENTRIES: &[::vrs_core_sdk::batch::Entry] = &[#(#entries),*];
+    });
+    let batch: ItemFn = parse_quote! {
+        fn _batch(
+            calls: Vec<::vrs_core_sdk::batch::Call>,
+        ) -> Result<Vec<::vrs_core_sdk::batch::Outcome>, String> {
+            ::vrs_core_sdk::batch::run(__VRS_BATCH_ENTRIES, calls).map_err(|e| e.to_string())
+        }
+    };
+    items.extend(export_as(
+        expand(batch, format_ident!("__vrs_batch")),
+        "__nucleus_post___batch",
+    ));
+}
+
+fn push_migrations(items: &mut Vec<syn::Item>, migrations: Vec<Expr>) {
+    for item in items.iter_mut() {
+        if let syn::Item::Fn(ref mut func) = item {
+            if func.attrs.iter().any(|a| a.path().is_ident("init")) {
+                // a unit init only logs the failure, otherwise the error is returned through
+                // `From<RuntimeError>`, e.g. by an init returning `CallResult<()>`
+                let on_error: syn::Stmt = match func.sig.output {
+                    ReturnType::Default => parse_quote! { return; },
+                    ReturnType::Type(..) => parse_quote! {
+                        return Err(::core::convert::From::from(e));
+                    },
+                };
+                // run the pending migrations before the init function touches the storage
+                func.block.stmts.insert(
+                    0,
+                    parse_quote! {
+                        if let Err(e) = ::vrs_core_sdk::storage::migration::run(__VRS_MIGRATIONS) {
+                            ::vrs_core_sdk::eprintln!("storage migration failed: {}", e);
+                            #on_error
+                        }
+                    },
+                );
+            }
+        }
+    }
+    items.push(parse_quote! {
+        #[doc(hidden)]
+        pub const __VRS_MIGRATIONS: &[::vrs_core_sdk::storage::migration::Migration] =
+            &[#(#migrations),*];
+    });
+    let upgrade: ItemFn = parse_quote! {
+        fn upgrade() -> ::vrs_core_sdk::CallResult<u32> {
+            ::vrs_core_sdk::storage::migration::run(__VRS_MIGRATIONS)
+        }
+    };
+    items.extend(export_as(
+        expand(upgrade, format_ident!("__vrs_upgrade")),
+        "__nucleus_upgrade",
+    ));
+}
+
+/// Export a generated entry under `symbol`, so the name of the item in the module can be
+/// prefixed with `__vrs_` without changing the symbol called by the host.
+fn export_as(entry: TokenStream, symbol: &str) -> Vec<syn::Item> {
+    let mut items = syn::parse::<syn::File>(entry)
+        .expect("generated entry should be valid")
+        .items;
+    for item in items.iter_mut() {
+        if let syn::Item::Fn(ref mut func) = item {
+            if func.attrs.iter().any(|a| a.path().is_ident("no_mangle")) {
+                func.attrs.retain(|a| !a.path().is_ident("no_mangle"));
+                func.attrs.push(parse_quote! { #[export_name = #symbol] });
+                func.attrs.push(parse_quote! { #[doc(hidden)] });
+            }
+        }
+    }
+    items
+}
+
+type NucleusArgs = Punctuated<syn::Meta, Token![,]>;
+
+/// `#[nucleus(batch)]` exports the `_batch` post function of the module.
+fn nucleus_batch(args: NucleusArgs) -> bool {
+    let mut batch = false;
+    for arg in args {
+        match arg {
+            syn::Meta::Path(ref path) if path.is_ident("batch") => batch = true,
+            _ => panic!("unknown nucleus argument, expected `batch`"),
+        }
+    }
+    batch
+}
+
+type PostArgs = Punctuated<syn::Meta, Token![,]>;
+
+/// `#[post(idempotent, ttl = <seconds>)]`
+struct Idempotency {
+    ttl: Option<u64>,
+}
+
+fn idempotency(args: PostArgs) -> Option<Idempotency> {
+    let (mut idempotent, mut ttl) = (false, None);
+    for arg in args {
+        match arg {
+            syn::Meta::Path(ref path) if path.is_ident("idempotent") => idempotent = true,
+            syn::Meta::NameValue(ref nv) if nv.path.is_ident("ttl") => match nv.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::Int(ref v),
+                    ..
+                }) => ttl = Some(v.base10_parse::<u64>().expect("ttl should be u64 seconds")),
+                _ => panic!("ttl should be an integer literal of seconds"),
+            },
+            _ => panic!("unknown post argument, expected `idempotent` or `ttl`"),
+        }
+    }
+    if ttl.is_some() && !idempotent {
+        panic!("ttl is only allowed for an idempotent function");
+    }
+    idempotent.then_some(Idempotency { ttl })
+}
+
 #[proc_macro_attribute]
-pub fn post(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn post(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr with PostArgs::parse_terminated);
     let func = parse_macro_input!(item as ItemFn);
     let func_name = format_ident!("__nucleus_{}_{}", "post", &func.sig.ident);
-    expand(func, func_name)
+    let idempotency = idempotency(args);
+    if idempotency.is_some() {
+        if func.sig.inputs.is_empty() {
+            panic!("idempotent function should take the idempotency key as its first parameter");
+        }
+        if !matches!(func.sig.output, ReturnType::Type(_, ref ty) if is_fallible(ty)) {
+            panic!("idempotent function should return a Result whose error is From<RuntimeError>");
+        }
+    }
+    expand_with(func, func_name, idempotency)
 }
 
 #[proc_macro_attribute]
@@ -139,6 +463,16 @@ pub fn init(_attr: TokenStream, item: TokenStream) -> TokenStream {
     expand(func, func_name)
 }
 
+#[proc_macro_attribute]
+pub fn migration(attr: TokenStream, item: TokenStream) -> TokenStream {
+    migration_versions(parse_macro_input!(attr with MigrationArgs::parse_terminated));
+    let func = parse_macro_input!(item as ItemFn);
+    if !func.sig.inputs.is_empty() {
+        panic!("migration should not have parameters");
+    }
+    quote! { #func }.into()
+}
+
 #[proc_macro_attribute]
 pub fn timer(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let func = parse_macro_input!(item as ItemFn);
@@ -154,6 +488,10 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
 }
 
 fn expand(func: ItemFn, entry_name: Ident) -> TokenStream {
+    expand_with(func, entry_name, None)
+}
+
+fn expand_with(func: ItemFn, entry_name: Ident, idempotency: Option<Idempotency>) -> TokenStream {
     let func_block = &func.block;
     let func_decl = &func.sig;
     let origin_name = &func_decl.ident;
@@ -181,23 +519,77 @@ fn expand(func: ItemFn, entry_name: Ident) -> TokenStream {
         ReturnType::Default => quote! { () },
         ReturnType::Type(_, ty) => quote! { #ty },
     };
+    // the encoded return value and whether it is an `Err`
+    let failed = match func_output {
+        ReturnType::Type(_, ty) if is_fallible(ty) => quote! { ret.is_err() },
+        _ => quote! { false },
+    };
+    let call = match idempotency {
+        None => quote! {
+            let ret = #origin_name(decoded);
+            (<#out_ty as ::vrs_core_sdk::codec::Encode>::encode(&ret), #failed)
+        },
+        Some(Idempotency { ttl }) => {
+            let name = origin_name.to_string();
+            let ttl = match ttl {
+                Some(secs) => quote! { ::std::time::Duration::from_secs(#secs) },
+                None => quote! { ::vrs_core_sdk::storage::idempotency::DEFAULT_TTL },
+            };
+            quote! {
+                // the first parameter is the idempotency key
+                let key = ::vrs_core_sdk::codec::Encode::encode(&decoded.0);
+                let recorded = ::vrs_core_sdk::storage::idempotency::recorded(#name, &key);
+                let result = match recorded {
+                    Ok(Some(recorded)) => Ok((recorded, false)),
+                    // the call is rolled back if its result can't be recorded
+                    Ok(None) => ::vrs_core_sdk::storage::transaction(
+                        || -> ::vrs_core_sdk::CallResult<(Vec<u8>, bool)> {
+                            let ret = #origin_name(decoded);
+                            let encoded =
+                                <#out_ty as ::vrs_core_sdk::codec::Encode>::encode(&ret);
+                            // only a success is recorded, a failed call may be retried with the key
+                            if ret.is_ok() {
+                                ::vrs_core_sdk::storage::idempotency::record(
+                                    #name, &key, &encoded, #ttl,
+                                )?;
+                                Ok((encoded, false))
+                            } else {
+                                Ok((encoded, true))
+                            }
+                        },
+                    )
+                    .and_then(|result| result),
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(output) => output,
+                    Err(e) => {
+                        let ret: #out_ty = Err(::core::convert::From::from(e));
+                        (<#out_ty as ::vrs_core_sdk::codec::Encode>::encode(&ret), true)
+                    }
+                }
+            }
+        }
+    };
+    let dispatch_name = format_ident!("__vrs_dispatch{}", entry_name);
     let expanded = quote! {
+        // decode the arguments, call the original function and encode its return value,
+        // `None` if the arguments can't be decoded
+        #[doc(hidden)]
+        pub fn #dispatch_name(mut __input: &[u8]) -> Option<(Vec<u8>, bool)> {
+            // rewrite the original function `fn(x: X, y: Y)` to `fn((x, y, z): (X, Y, Z))`
+            fn #origin_name((#(#arg_names,)*): (#(#tys,)*)) #func_output #func_block
+            let decoded =
+                <(#(#tys,)*) as ::vrs_core_sdk::codec::Decode>::decode(&mut __input).ok()?;
+            Some({ #call })
+        }
+
         // declare the wrapper function: `fn __nucleus_XX(__ptr: *const u8, __len: usize)`
         #[no_mangle]
         pub fn #entry_name(__ptr: *const u8, __len: usize) -> *const u8 {
-            // rewrite the original function `fn(x: X, y: Y)` to `fn((x, y, z): (X, Y, Z))`
-            fn #origin_name((#(#arg_names,)*): (#(#tys,)*)) #func_output #func_block
             // the VM has passed the raw parameters, now decode them within VM
-            let mut v = unsafe { std::slice::from_raw_parts(__ptr, __len) };
-            let decoding_result = <(#(#tys,)*) as ::vrs_core_sdk::codec::Decode>::decode(&mut v);
-            let result: Option<Vec<u8>> = match decoding_result {
-                Ok(decoded) => {
-                    let ret = #origin_name(decoded);
-                    let encoded = <#out_ty as ::vrs_core_sdk::codec::Encode>::encode(&ret);
-                    Option::<Vec<u8>>::Some(encoded)
-                }
-                Err(_) => None::<Vec<u8>>,
-            };
+            let input = unsafe { std::slice::from_raw_parts(__ptr, __len) };
+            let result: Option<Vec<u8>> = #dispatch_name(input).map(|(encoded, _)| encoded);
             let encoded = <Option<Vec<u8>> as ::vrs_core_sdk::codec::Encode>::encode(&result);
             let len = encoded.len() as u32;
             let mut output = Vec::with_capacity(4 + len as usize);
@@ -210,3 +602,323 @@ fn expand(func: ItemFn, entry_name: Ident) -> TokenStream {
     };
     expanded.into()
 }
+
+fn key_trait_generics(generics: &syn::Generics, bound: syn::Path) -> syn::Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+/// the bindings of the fields, `Self { a, b }` or `Self(f0, f1)`
+fn field_bindings(fields: &Fields) -> (proc_macro2::TokenStream, Vec<Ident>) {
+    match fields {
+        Fields::Named(named) => {
+            let names: Vec<_> = named
+                .named
+                .iter()
+                .map(|f| f.ident.clone().expect("named field; qed"))
+                .collect();
+            (quote! { { #(#names),* } }, names)
+        }
+        Fields::Unnamed(unnamed) => {
+            let names: Vec<_> = (0..unnamed.unnamed.len())
+                .map(|i| format_ident!("__f{}", i))
+                .collect();
+            (quote! { ( #(#names),* ) }, names)
+        }
+        Fields::Unit => (quote! {}, vec![]),
+    }
+}
+
+fn fields_decoder(fields: &Fields) -> proc_macro2::TokenStream {
+    let decode = quote! { ::vrs_core_sdk::storage::key::KeyDecode::decode_key_from(input)? };
+    match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|f| &f.ident);
+            quote! { { #(#names: #decode),* } }
+        }
+        Fields::Unnamed(unnamed) => {
+            let decodes = unnamed.unnamed.iter().map(|_| &decode);
+            quote! { ( #(#decodes),* ) }
+        }
+        Fields::Unit => quote! {},
+    }
+}
+
+#[proc_macro_derive(KeyEncode)]
+pub fn derive_key_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let generics = key_trait_generics(
+        &input.generics,
+        parse_quote!(::vrs_core_sdk::storage::key::KeyEncode),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, names) = field_bindings(&data.fields);
+            quote! {
+                let Self #pattern = self;
+                #(::vrs_core_sdk::storage::key::KeyEncode::encode_key_to(#names, out);)*
+            }
+        }
+        Data::Enum(data) => {
+            if data.variants.len() > 256 {
+                panic!("KeyEncode supports at most 256 variants");
+            }
+            let arms = data.variants.iter().enumerate().map(|(index, variant)| {
+                let index = index as u8;
+                let variant_name = &variant.ident;
+                let (pattern, names) = field_bindings(&variant.fields);
+                quote! {
+                    Self::#variant_name #pattern => {
+                        out.push(#index);
+                        #(::vrs_core_sdk::storage::key::KeyEncode::encode_key_to(#names, out);)*
+                    }
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => panic!("KeyEncode can't be derived for unions"),
+    };
+    quote! {
+        impl #impl_generics ::vrs_core_sdk::storage::key::KeyEncode for #name #ty_generics #where_clause {
+            fn encode_key_to(&self, out: &mut Vec<u8>) {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(KeyDecode)]
+pub fn derive_key_decode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let generics = key_trait_generics(
+        &input.generics,
+        parse_quote!(::vrs_core_sdk::storage::key::KeyDecode),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let decoder = fields_decoder(&data.fields);
+            quote! { Ok(Self #decoder) }
+        }
+        Data::Enum(data) => {
+            if data.variants.len() > 256 {
+                panic!("KeyDecode supports at most 256 variants");
+            }
+            let arms = data.variants.iter().enumerate().map(|(index, variant)| {
+                let index = index as u8;
+                let variant_name = &variant.ident;
+                let decoder = fields_decoder(&variant.fields);
+                quote! { #index => Ok(Self::#variant_name #decoder), }
+            });
+            quote! {
+                match <u8 as ::vrs_core_sdk::storage::key::KeyDecode>::decode_key_from(input)? {
+                    #(#arms)*
+                    _ => Err("invalid variant in the key".into()),
+                }
+            }
+        }
+        Data::Union(_) => panic!("KeyDecode can't be derived for unions"),
+    };
+    quote! {
+        impl #impl_generics ::vrs_core_sdk::storage::key::KeyDecode for #name #ty_generics #where_clause {
+            fn decode_key_from(
+                input: &mut &[u8],
+            ) -> Result<Self, ::vrs_core_sdk::storage::key::Error> {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+struct EntityIndex {
+    field: Ident,
+    ty: Type,
+    unique: bool,
+}
+
+/// Derive a repository `<Name>Repository` storing the struct under a per-entity prefix.
+///
+/// The entries are stored under `<prefix>:pk:` ++ primary key and each `#[index]` field is
+/// indexed under `<prefix>:idx:<field>:`, the prefix defaults to the name of the struct and
+/// can be changed with `#[entity(prefix = "...")]`.
+#[proc_macro_derive(Entity, attributes(entity, primary_key, index))]
+pub fn derive_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    if !input.generics.params.is_empty() {
+        panic!("entity should not have generics");
+    }
+    let name = &input.ident;
+    let vis = &input.vis;
+    let repository = format_ident!("{}Repository", name);
+    let mut prefix = name.to_string();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("entity")) {
+        let args = attr
+            .parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)
+            .expect("expected `#[entity(prefix = \"...\")]`");
+        for arg in args {
+            match arg.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::Str(ref v),
+                    ..
+                }) if arg.path.is_ident("prefix") => prefix = v.value(),
+                _ => panic!("expected `#[entity(prefix = \"...\")]`"),
+            }
+        }
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => panic!("entity should have named fields"),
+        },
+        _ => panic!("entity should be a struct"),
+    };
+    let mut primary_key = None;
+    let mut indexes = vec![];
+    for field in fields {
+        let ident = field.ident.clone().expect("named field; qed");
+        for attr in &field.attrs {
+            if attr.path().is_ident("primary_key") {
+                if primary_key.is_some() {
+                    panic!("entity should have exactly one #[primary_key]");
+                }
+                primary_key = Some((ident.clone(), field.ty.clone()));
+            } else if attr.path().is_ident("index") {
+                let unique = match attr.meta {
+                    syn::Meta::Path(_) => false,
+                    _ => {
+                        let arg: Ident = attr
+                            .parse_args()
+                            .expect("expected `#[index]` or `#[index(unique)]`");
+                        if arg != "unique" {
+                            panic!("expected `#[index]` or `#[index(unique)]`");
+                        }
+                        true
+                    }
+                };
+                indexes.push(EntityIndex {
+                    field: ident.clone(),
+                    ty: field.ty.clone(),
+                    unique,
+                });
+            }
+        }
+    }
+    let (pk, pk_ty) = primary_key.expect("entity should have exactly one #[primary_key]");
+    let pk_prefix = format!("{}:pk:", prefix);
+    let index_consts = indexes.iter().map(|index| {
+        let field = &index.field;
+        let ty = &index.ty;
+        let const_name = format_ident!("BY_{}", field.to_string().to_uppercase());
+        let index_prefix = format!("{}:idx:{}:", prefix, field);
+        let index_ty = if index.unique {
+            quote! { ::vrs_core_sdk::storage::UniqueIndex }
+        } else {
+            quote! { ::vrs_core_sdk::storage::Index }
+        };
+        quote! {
+            pub const #const_name: #index_ty<#pk_ty, #name, #ty> =
+                #index_ty::new(#index_prefix.as_bytes(), |e| ::core::clone::Clone::clone(&e.#field));
+        }
+    });
+    let index_refs = indexes.iter().map(|index| {
+        let const_name = format_ident!("BY_{}", index.field.to_string().to_uppercase());
+        quote! { &Self::#const_name }
+    });
+    let finders = indexes.iter().map(|index| {
+        let field = &index.field;
+        let ty = &index.ty;
+        let const_name = format_ident!("BY_{}", field.to_string().to_uppercase());
+        let finder = format_ident!("find_by_{}", field);
+        if index.unique {
+            quote! {
+                pub fn #finder(#field: &#ty) -> ::vrs_core_sdk::CallResult<Option<#name>> {
+                    match Self::#const_name.lookup(#field)? {
+                        Some(key) => Self::STORE.get(&key),
+                        None => Ok(None),
+                    }
+                }
+            }
+        } else {
+            quote! {
+                pub fn #finder(
+                    #field: &#ty,
+                    limit: usize,
+                ) -> ::vrs_core_sdk::CallResult<Vec<#name>> {
+                    let mut found = vec![];
+                    for key in Self::#const_name.lookup(#field, limit)? {
+                        found.extend(Self::STORE.get(&key)?);
+                    }
+                    Ok(found)
+                }
+            }
+        }
+    });
+    quote! {
+        #vis struct #repository;
+
+        impl #repository {
+            pub const PREFIX: &'static [u8] = #prefix.as_bytes();
+
+            #(#index_consts)*
+
+            pub const STORE: ::vrs_core_sdk::storage::StorageMap<#pk_ty, #name> =
+                ::vrs_core_sdk::storage::StorageMap::with_indexes(
+                    #pk_prefix.as_bytes(),
+                    &[#(#index_refs),*],
+                );
+
+            /// Insert a new entity, fails with `AlreadyExists` if the primary key is taken.
+            pub fn insert(entity: &#name) -> ::vrs_core_sdk::CallResult<()> {
+                if Self::STORE.contains_key(&entity.#pk)? {
+                    return Err(::vrs_core_sdk::error::RuntimeError::AlreadyExists);
+                }
+                Self::STORE.insert(&entity.#pk, entity)
+            }
+
+            pub fn get(#pk: &#pk_ty) -> ::vrs_core_sdk::CallResult<Option<#name>> {
+                Self::STORE.get(#pk)
+            }
+
+            /// Update an existing entity, fails with `NotFound` if the primary key is absent.
+            pub fn update(entity: &#name) -> ::vrs_core_sdk::CallResult<()> {
+                if !Self::STORE.contains_key(&entity.#pk)? {
+                    return Err(::vrs_core_sdk::error::RuntimeError::NotFound);
+                }
+                Self::STORE.insert(&entity.#pk, entity)
+            }
+
+            /// Delete an entity, fails with `NotFound` if the primary key is absent.
+            pub fn delete(#pk: &#pk_ty) -> ::vrs_core_sdk::CallResult<()> {
+                if !Self::STORE.contains_key(#pk)? {
+                    return Err(::vrs_core_sdk::error::RuntimeError::NotFound);
+                }
+                Self::STORE.remove(#pk)
+            }
+
+            /// List a page of at most `limit` entities ordered by primary key.
+            pub fn list(
+                cursor: Option<&::vrs_core_sdk::storage::Cursor>,
+                limit: usize,
+            ) -> ::vrs_core_sdk::CallResult<::vrs_core_sdk::storage::Page<#name>> {
+                Ok(Self::STORE
+                    .page(cursor, limit, ::vrs_core_sdk::storage::Direction::Forward)?
+                    .map(|(_, entity)| entity))
+            }
+
+            #(#finders)*
+        }
+    }
+    .into()
+}
@@ -10,16 +10,137 @@ pub struct ApiEntry {
     pub return_type: u32,
 }
 
+/// The layout of the entries of a typed storage container.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Encode, Decode, Eq, PartialEq)]
+pub enum StorageKind {
+    /// `prefix ++ key -> value`, the value starts with a
+    /// [compression](crate::storage::compress) header if `compressed`.
+    Map { compressed: bool },
+    /// `prefix ++ key -> value` where the value is [encrypted](crate::storage::sealed) and only
+    /// readable by the nucleus.
+    SealedMap,
+    /// `prefix ++ "i" ++ index -> item` of a `Queue` or a `Deque`, the item starts with a
+    /// compression header if `compressed`.
+    Deque { compressed: bool },
+    /// `prefix ++ "m" ++ member -> score` of a `SortedSet`, the score is encoded as a key.
+    SortedSet,
+    /// `prefix ++ "d" ++ index -> data` of a `MerkleLog`, the data is stored as is.
+    MerkleLog,
+    /// `prefix ++ "e" ++ stream ++ version -> event` of an `EventStore`.
+    EventStore,
+}
+
+/// A typed storage container declared by the nucleus.
+///
+/// The key following the prefix is encoded with
+/// [`KeyEncode`](crate::storage::key::KeyEncode), the value is SCALE encoded unless stated
+/// otherwise by the [`StorageKind`].
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Encode, Decode, Eq, PartialEq)]
+pub struct StorageEntry {
+    pub name: String,
+    pub kind: StorageKind,
+    pub prefix: Vec<u8>,
+    pub key_type: u32,
+    pub value_type: u32,
+}
+
+/// Implemented by the typed storage containers to describe themselves in the ABI.
+///
+/// The `#[nucleus]` macro registers every container declared as a `const` of the module, so
+/// their key and value types should derive `TypeInfo`.
+///
+/// # Examples
+///
+/// ```
+/// use vrs_core_sdk::abi::StorageKind;
+/// use vrs_core_sdk::codec::{Decode, Encode};
+/// use vrs_core_sdk::scale_info::TypeInfo;
+/// use vrs_core_sdk::{nucleus, storage::StorageMap};
+///
+/// #[nucleus]
+/// pub mod nucleus {
+///     use super::*;
+///
+///     #[derive(Decode, Encode, TypeInfo)]
+///     #[codec(crate = vrs_core_sdk::codec)]
+///     #[scale_info(crate = vrs_core_sdk::scale_info)]
+///     pub struct User {
+///         pub name: String,
+///     }
+///
+///     pub const USERS: StorageMap<u64, User> = StorageMap::new(b"user:");
+///
+///     pub fn abi() -> vrs_core_sdk::abi::JsonAbi {
+///         TYPES.dump_abi()
+///     }
+/// }
+///
+/// # fn main() {
+/// let abi = nucleus::abi();
+/// assert_eq!(abi.storage[0].name, "USERS");
+/// assert_eq!(abi.storage[0].kind, StorageKind::Map { compressed: false });
+/// assert_eq!(abi.storage[0].prefix, b"user:");
+/// let user = abi.types.iter().find(|t| t.id == abi.storage[0].value_type).unwrap();
+/// assert_eq!(user.ty.path.segments.last().unwrap(), "User");
+/// // `_batch` is only exported by `#[nucleus(batch)]`
+/// assert!(abi.functions.iter().all(|f| f.name != "_batch"));
+/// # }
+/// ```
+pub trait StorageLayout {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry);
+}
+
 pub struct ApiRegistry {
     pub types: Registry,
     pub entries: Vec<ApiEntry>,
+    pub storage: Vec<StorageEntry>,
 }
 
-#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize, Decode))]
+/// The ABI returned by the `__nucleus_abi` export, SCALE encoded.
+///
+/// The ABI of a nucleus built before the `storage` field was added still decodes, in SCALE or
+/// JSON, with an empty `storage`:
+///
+/// ```
+/// # #[cfg(feature = "std")]
+/// # {
+/// use vrs_core_sdk::abi::{AbiType, ApiEntry, JsonAbi};
+/// use vrs_core_sdk::codec::{Decode, Encode};
+///
+/// let old = (Vec::<ApiEntry>::new(), Vec::<AbiType>::new()).encode();
+/// assert!(JsonAbi::decode(&mut &old[..]).unwrap().storage.is_empty());
+/// let old: JsonAbi = serde_json::from_str(r#"{"functions": [], "types": []}"#).unwrap();
+/// assert!(old.storage.is_empty());
+/// # }
+/// ```
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Encode, Eq, PartialEq)]
 pub struct JsonAbi {
     pub functions: Vec<ApiEntry>,
     pub types: Vec<AbiType>,
+    /// added after the first release, empty when decoding the ABI of an older nucleus
+    #[cfg_attr(feature = "std", serde(default))]
+    pub storage: Vec<StorageEntry>,
+}
+
+#[cfg(feature = "std")]
+impl Decode for JsonAbi {
+    fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+        let functions = Decode::decode(input)?;
+        let types = Decode::decode(input)?;
+        // an older nucleus encodes its ABI without the storage
+        let storage = match input.remaining_len()? {
+            Some(0) => vec![],
+            _ => Decode::decode(input)?,
+        };
+        Ok(Self {
+            functions,
+            types,
+            storage,
+        })
+    }
 }
 
 impl JsonAbi {
@@ -41,6 +162,7 @@ impl ApiRegistry {
         Self {
             types: Registry::new(),
             entries: Vec::new(),
+            storage: Vec::new(),
         }
     }
 
@@ -67,6 +189,26 @@ impl ApiRegistry {
         self.entries.push(entry);
     }
 
+    /// Register a storage container, see [`StorageLayout`].
+    pub fn register_storage(
+        &mut self,
+        name: String,
+        kind: StorageKind,
+        prefix: &[u8],
+        key_type: MetaType,
+        value_type: MetaType,
+    ) {
+        let key_type = self.types.register_type(&key_type).id;
+        let value_type = self.types.register_type(&value_type).id;
+        self.storage.push(StorageEntry {
+            name,
+            kind,
+            prefix: prefix.to_vec(),
+            key_type,
+            value_type,
+        });
+    }
+
     pub fn dump_abi(&self) -> JsonAbi {
         let types = self
             .types
@@ -79,6 +221,7 @@ impl ApiRegistry {
         JsonAbi {
             functions: self.entries.clone(),
             types,
+            storage: self.storage.clone(),
         }
     }
 }
@@ -0,0 +1,145 @@
+//! Calling several post functions atomically.
+//!
+//! `#[nucleus(batch)]` generates a `_batch` post function, exported as
+//! `__nucleus_post___batch`, which takes a list of `(function name, SCALE encoded arguments)`
+//! of the post functions of the module. The calls are executed in order within a
+//! [`transaction`](crate::storage::transaction) and stop at the first failure, in which case
+//! the storage changes of all the calls are discarded. A call fails if the function is unknown,
+//! its arguments can't be decoded, or it returns the `Err` of a `Result` or `CallResult`.
+//!
+//! The batch is opt-in since the transactions are only provided by the length-aware host, a
+//! `#[nucleus]` module without the argument exports no `_batch` function.
+//!
+//! Only the storage changes are discarded, the requests already sent by the calls, e.g. by
+//! `http` or `tss`, are not revoked.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::nucleus;
+//!
+//! #[nucleus(batch)]
+//! pub mod nucleus {
+//!     use vrs_core_sdk::{post, storage};
+//!
+//!     #[post]
+//!     pub fn deposit(account: String, amount: i64) -> Result<i64, String> {
+//!         storage::increment(account, amount).map_err(|e| e.to_string())
+//!     }
+//!
+//!     #[post]
+//!     pub fn withdraw(account: String, amount: i64) -> Result<i64, String> {
+//!         let rest = storage::increment(account, -amount).map_err(|e| e.to_string())?;
+//!         if rest < 0 {
+//!             return Err("insufficient balance".to_string());
+//!         }
+//!         Ok(rest)
+//!     }
+//! }
+//!
+//! // `[("deposit", ("bob", 5)), ("withdraw", ("alice", 5))]` moves 5 from alice to bob, or
+//! // changes nothing if alice doesn't have enough
+//! # fn main() {}
+//! ```
+//!
+//! With the `mock` feature, a failed call discards the changes of the previous ones:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::batch::{self, Outcome};
+//! use vrs_core_sdk::codec::Encode;
+//! use vrs_core_sdk::{mock, nucleus, storage};
+//!
+//! #[nucleus(batch)]
+//! mod nucleus {
+//!     use vrs_core_sdk::{post, storage};
+//!
+//!     #[post]
+//!     pub fn withdraw(account: String, amount: i64) -> Result<i64, String> {
+//!         let rest = storage::increment(account, -amount).map_err(|e| e.to_string())?;
+//!         (rest >= 0).then_some(rest).ok_or("insufficient balance".to_string())
+//!     }
+//! }
+//!
+//! mock::reset();
+//! storage::put(b"alice", 5i64.encode()).unwrap();
+//! let withdraw = |amount: i64| ("withdraw".to_string(), ("alice", amount).encode());
+//! let calls = vec![withdraw(3), withdraw(3), withdraw(1)];
+//! let outcomes = batch::run(nucleus::__VRS_BATCH_ENTRIES, calls).unwrap();
+//! let failed = Err::<i64, String>("insufficient balance".to_string()).encode();
+//! assert_eq!(
+//!     outcomes,
+//!     vec![Outcome::Succeeded(Ok::<i64, String>(2).encode()), Outcome::Failed(failed), Outcome::Skipped]
+//! );
+//! assert_eq!(storage::increment(b"alice", 0).unwrap(), 5);
+//!
+//! let calls = vec![withdraw(3), withdraw(2)];
+//! let outcomes = batch::run(nucleus::__VRS_BATCH_ENTRIES, calls).unwrap();
+//! assert!(outcomes.iter().all(|o| matches!(o, Outcome::Succeeded(_))));
+//! assert_eq!(storage::increment(b"alice", 0).unwrap(), 0);
+//!
+//! let unknown = ("deposit".to_string(), vec![]);
+//! let invalid = ("withdraw".to_string(), vec![]);
+//! assert_eq!(batch::run(nucleus::__VRS_BATCH_ENTRIES, vec![unknown]).unwrap(), vec![Outcome::Invalid]);
+//! assert_eq!(batch::run(nucleus::__VRS_BATCH_ENTRIES, vec![invalid]).unwrap(), vec![Outcome::Invalid]);
+//! # }
+//! ```
+
+use crate::{storage, CallResult};
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+
+/// The name of a post function and its SCALE encoded arguments.
+pub type Call = (String, Vec<u8>);
+
+/// The outcome of a call of a batch.
+#[derive(Debug, Clone, Decode, Encode, TypeInfo, Eq, PartialEq)]
+pub enum Outcome {
+    /// the encoded return value of the function
+    Succeeded(Vec<u8>),
+    /// the encoded return value of the function, which is an `Err`
+    Failed(Vec<u8>),
+    /// the function is unknown or its arguments can't be decoded
+    Invalid,
+    /// not executed since a previous call failed
+    Skipped,
+}
+
+/// Decode the arguments and call a post function, returning its encoded return value and
+/// whether it is an `Err`, `None` if the arguments can't be decoded.
+pub type Dispatch = fn(&[u8]) -> Option<(Vec<u8>, bool)>;
+
+/// A post function which can be called in a batch, generated by `#[nucleus(batch)]`.
+pub struct Entry {
+    pub name: &'static str,
+    pub call: Dispatch,
+}
+
+/// Execute `calls` in a transaction, see the [module](self) documentation.
+pub fn run(entries: &[Entry], calls: Vec<Call>) -> CallResult<Vec<Outcome>> {
+    let mut outcomes = Vec::with_capacity(calls.len());
+    let result = storage::transaction(|| {
+        for (name, args) in &calls {
+            let output = entries
+                .iter()
+                .find(|entry| entry.name == name)
+                .and_then(|entry| (entry.call)(args));
+            let outcome = match output {
+                Some((ret, false)) => Outcome::Succeeded(ret),
+                Some((ret, true)) => Outcome::Failed(ret),
+                None => Outcome::Invalid,
+            };
+            let failed = !matches!(outcome, Outcome::Succeeded(_));
+            outcomes.push(outcome);
+            if failed {
+                return Err(());
+            }
+        }
+        Ok(())
+    })?;
+    if result.is_err() {
+        outcomes.resize(calls.len(), Outcome::Skipped);
+    }
+    Ok(outcomes)
+}
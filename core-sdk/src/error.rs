@@ -1,6 +1,7 @@
 use codec::{Decode, Encode};
+use scale_info::TypeInfo;
 
-#[derive(Debug, Clone, Decode, Encode)]
+#[derive(Debug, Clone, Decode, Encode, TypeInfo)]
 pub enum RuntimeError {
     #[codec(index = 0)]
     DecodeReturnValueError,
@@ -16,6 +17,26 @@ pub enum RuntimeError {
     TimerError(String),
     #[codec(index = 6)]
     TssError(String),
+    #[codec(index = 7)]
+    LimitExceeded,
+    #[codec(index = 8)]
+    KeyTooLarge,
+    #[codec(index = 9)]
+    ValueTooLarge,
+    #[codec(index = 10)]
+    UnexpectedHostStatus(i32),
+    #[codec(index = 11)]
+    UniqueViolation,
+    #[codec(index = 12)]
+    AlreadyExists,
+    #[codec(index = 13)]
+    NotFound,
+    #[codec(index = 14)]
+    InvalidCursor,
+    #[codec(index = 15)]
+    QuotaExceeded,
+    #[codec(index = 16)]
+    HostLengthMismatch { expected: i32, reported: i32 },
 }
 
 impl core::fmt::Display for RuntimeError {
@@ -28,6 +49,20 @@ impl core::fmt::Display for RuntimeError {
             RuntimeError::HttpError(e) => write!(f, "Http error: {}", e),
             RuntimeError::TimerError(e) => write!(f, "Timer error: {}", e),
             RuntimeError::TssError(e) => write!(f, "Tss error: {}", e),
+            RuntimeError::LimitExceeded => write!(f, "Limit exceeds maximum allowed size"),
+            RuntimeError::KeyTooLarge => write!(f, "Key exceeds maximum allowed size"),
+            RuntimeError::ValueTooLarge => write!(f, "Value exceeds maximum allowed size"),
+            RuntimeError::UnexpectedHostStatus(s) => write!(f, "Unexpected host status: {}", s),
+            RuntimeError::UniqueViolation => write!(f, "Unique index violation"),
+            RuntimeError::AlreadyExists => write!(f, "Entry already exists"),
+            RuntimeError::NotFound => write!(f, "Entry not found"),
+            RuntimeError::InvalidCursor => write!(f, "Invalid or tampered cursor"),
+            RuntimeError::QuotaExceeded => write!(f, "Storage quota exceeded"),
+            RuntimeError::HostLengthMismatch { expected, reported } => write!(
+                f,
+                "Host result length changed from {} to {} between calls",
+                expected, reported
+            ),
         }
     }
 }
@@ -42,6 +42,19 @@ pub struct HttpResponse {
     pub body: Vec<u8>,
 }
 
+#[cfg(not(feature = "legacy-host"))]
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn http_request_v2(
+        req_ptr: *const u8,
+        req_len: u32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+}
+
+#[cfg(feature = "legacy-host")]
 #[link(wasm_import_module = "env")]
 extern "C" {
     fn http_request(req_ptr: *const u8, req_len: u32, return_ptr: *mut u8) -> i32;
@@ -83,15 +96,13 @@ extern "C" {
 /// ```
 pub fn request(request: HttpRequest) -> Result<u64, RuntimeError> {
     let bytes = request.encode();
-    let mut return_bytes = crate::allocate_buffer();
-    let status = unsafe {
-        http_request(
-            bytes.as_ptr(),
-            bytes.len() as u32,
-            return_bytes.as_mut_ptr(),
-        )
-    };
-    assert!(status == crate::NO_MORE_DATA);
-    Result::<u64, RuntimeError>::decode(&mut &return_bytes[..])
-        .map_err(|_| RuntimeError::DecodeReturnValueError)?
+    let len = crate::check_len(&bytes, RuntimeError::ValueTooLarge)? as u32;
+    #[cfg(not(feature = "legacy-host"))]
+    let return_bytes = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        http_request_v2(bytes.as_ptr(), len, ptr, cap, offset)
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let return_bytes =
+        crate::read_legacy_buffer(|ptr| unsafe { http_request(bytes.as_ptr(), len, ptr) })?;
+    crate::decode_host_result(&return_bytes)
 }
@@ -4,42 +4,55 @@
 //! # Examples
 //!
 //! ```
-//! use parity_scale_codec::{Decode, Encode};
-//! use vrs_core_sdk::{get, post, storage};
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::{get, post, storage::StorageMap};
 //!
 //! #[derive(Debug, Decode, Encode)]
+//! #[codec(crate = vrs_core_sdk::codec)]
 //! pub struct User {
-//!     pub id: u64,
 //!     pub name: String,
 //! }
 //!
+//! const USERS: StorageMap<u64, User> = StorageMap::new(b"user:");
+//!
 //! #[post]
 //! pub fn add_user(user: User) -> Result<u64, String> {
-//!     let max_id_key = [&b"user:"[..], &u64::MAX.to_be_bytes()[..]].concat();
-//!     let max_id = match storage::search(&max_id_key, storage::Direction::Reverse)
-//!         .map_err(|e| e.to_string())?
-//!     {
-//!         Some((id, _)) => u64::from_be_bytes(id[5..].try_into().unwrap()) + 1,
-//!         None => 1u64,
-//!     };
-//!     let key = [&b"user:"[..], &max_id.to_be_bytes()[..]].concat();
-//!     storage::put(&key, user.encode()).map_err(|e| e.to_string())?;
-//!     Ok(max_id)
+//!     USERS.insert_auto(&user).map_err(|e| e.to_string())
 //! }
 //!
 //! #[get]
 //! pub fn get_user(id: u64) -> Result<Option<User>, String> {
-//!     let key = [&b"user:"[..], &id.to_be_bytes()[..]].concat();
-//!     let r = storage::get(&key).map_err(|e| e.to_string())?;
-//!     let user = r.map(|d| User::decode(&mut &d[..]).unwrap());
-//!     Ok(user)
+//!     USERS.get(&id).map_err(|e| e.to_string())
 //! }
 //! ```
 
+/// Declare host functions which only the length-aware host provides. Under the `legacy-host`
+/// feature they aren't imported, and calling them returns [`HOST_FN_UNSUPPORTED`] so the SDK
+/// functions built on them fail with `RuntimeError::UnexpectedHostStatus`.
+macro_rules! length_aware_host_fns {
+    ($(fn $name:ident($($arg:ident: $ty:ty),* $(,)?) -> i32;)*) => {
+        #[cfg(not(feature = "legacy-host"))]
+        #[link(wasm_import_module = "env")]
+        extern "C" {
+            $(fn $name($($arg: $ty),*) -> i32;)*
+        }
+
+        $(
+            #[cfg(feature = "legacy-host")]
+            unsafe fn $name($(_: $ty),*) -> i32 {
+                $crate::HOST_FN_UNSUPPORTED
+            }
+        )*
+    };
+}
+
 pub mod abi;
+pub mod batch;
 pub mod error;
 pub mod http;
 pub mod io;
+#[cfg(all(feature = "mock", not(target_arch = "wasm32")))]
+pub mod mock;
 pub mod storage;
 pub mod timer;
 pub mod tss;
@@ -55,6 +68,14 @@ pub use vrs_core_macros::*;
 /// this should be equal to a page size
 pub const BUFFER_LEN: usize = 64 * 1024;
 
+/// the capacity offered to the host on the first call of a length-aware host function,
+/// results larger than this are fetched with exactly one more call
+pub const RETURN_BUFFER_LEN: usize = 1024;
+
+/// the status returned for the host functions which the legacy host doesn't provide, see the
+/// `legacy-host` feature
+pub const HOST_FN_UNSUPPORTED: i32 = i32::MIN;
+
 /// if host function returns this value, it means there is no more data to read
 pub const NO_MORE_DATA: i32 = 0;
 
@@ -64,7 +85,96 @@ pub type CallResult<T> = Result<T, error::RuntimeError>;
 /// the id of the nucleus, same as AccountId32
 pub type NucleusId = AccountId;
 
+#[cfg(feature = "legacy-host")]
 #[inline]
 pub(crate) fn allocate_buffer() -> Vec<u8> {
     vec![0u8; BUFFER_LEN]
 }
+
+/// Read the result of a legacy host function which writes at most `BUFFER_LEN` bytes.
+#[cfg(feature = "legacy-host")]
+pub(crate) fn read_legacy_buffer(host_fn: impl FnOnce(*mut u8) -> i32) -> CallResult<Vec<u8>> {
+    let mut buf = allocate_buffer();
+    let status = host_fn(buf.as_mut_ptr());
+    if status != NO_MORE_DATA {
+        return Err(error::RuntimeError::UnexpectedHostStatus(status));
+    }
+    Ok(buf)
+}
+
+/// Read the result of a legacy host function page by page, the host is called with the
+/// offset of the next page until it returns `NO_MORE_DATA`.
+#[cfg(feature = "legacy-host")]
+pub(crate) fn read_legacy_pages(
+    mut host_fn: impl FnMut(*mut u8, i32) -> i32,
+) -> CallResult<Vec<u8>> {
+    let mut buf = allocate_buffer();
+    let mut val = vec![];
+    loop {
+        let status = host_fn(buf.as_mut_ptr(), val.len() as i32);
+        val.extend_from_slice(&buf);
+        if status == NO_MORE_DATA {
+            break;
+        }
+    }
+    Ok(val)
+}
+
+/// Read the result of a length-aware host function.
+///
+/// The host function is called with `(return_ptr, return_cap, offset)`. It writes at most
+/// `return_cap` bytes of the encoded result starting from `offset` and returns the total
+/// length of the encoded result. The returned buffer holds exactly the encoded result.
+///
+/// A result longer than `RETURN_BUFFER_LEN` is fetched with a second call at the offset of
+/// the missing bytes. The host must serve that call from the result of the first one instead of
+/// running the function again, otherwise functions with side effects, e.g. `storage::take`
+/// or `storage::increment`, would apply them twice.
+pub(crate) fn read_host_buffer(
+    mut host_fn: impl FnMut(*mut u8, i32, i32) -> i32,
+) -> CallResult<Vec<u8>> {
+    let mut buf = Vec::<u8>::with_capacity(RETURN_BUFFER_LEN);
+    let status = host_fn(buf.as_mut_ptr(), RETURN_BUFFER_LEN as i32, 0);
+    if status < 0 {
+        return Err(error::RuntimeError::UnexpectedHostStatus(status));
+    }
+    let total = status as usize;
+    let written = total.min(RETURN_BUFFER_LEN);
+    unsafe { buf.set_len(written) };
+    if total > written {
+        buf.reserve_exact(total - written);
+        let reported = host_fn(
+            unsafe { buf.as_mut_ptr().add(written) },
+            (total - written) as i32,
+            written as i32,
+        );
+        if reported < 0 {
+            return Err(error::RuntimeError::UnexpectedHostStatus(reported));
+        }
+        if reported != status {
+            return Err(error::RuntimeError::HostLengthMismatch {
+                expected: status,
+                reported,
+            });
+        }
+        unsafe { buf.set_len(total) };
+    }
+    Ok(buf)
+}
+
+/// Check the length of a buffer passed to the host, the host functions take `i32` lengths.
+pub(crate) fn check_len(buf: &[u8], err: error::RuntimeError) -> CallResult<i32> {
+    i32::try_from(buf.len()).map_err(|_| err)
+}
+
+/// Decode the `CallResult` returned by a host function.
+///
+/// The legacy protocol pads the result with zeros so the trailing bytes are ignored, otherwise
+/// the buffer must hold exactly one encoded value.
+pub(crate) fn decode_host_result<T: codec::Decode>(buf: &[u8]) -> CallResult<T> {
+    #[cfg(feature = "legacy-host")]
+    let decoded = <CallResult<T> as codec::Decode>::decode(&mut &buf[..]);
+    #[cfg(not(feature = "legacy-host"))]
+    let decoded = <CallResult<T> as codec::DecodeAll>::decode_all(&mut &buf[..]);
+    decoded.map_err(|_| error::RuntimeError::DecodeReturnValueError)?
+}
@@ -0,0 +1,553 @@
+//! A mock host implementing the storage host functions in memory, so the storage semantics
+//! can be tested locally with `cargo test --features mock`. The clock returned by
+//! `timer::now` is provided too and only moves with [`set_now`], and `println!` and `eprintln!`
+//! write to the stdout and stderr of the test.
+//!
+//! The storage is kept per thread and starts empty, call [`reset`] to clear it between tests.
+//! A [`Snapshot`] can be loaded with [`load`] before a test, and compared with the one taken by
+//! [`snapshot`] after a call.
+//! Only the length-aware host functions are provided, the mock can't be combined with the
+//! `legacy-host` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::{mock, storage};
+//!
+//! mock::reset();
+//! storage::put(b"key", b"value").unwrap();
+//! assert_eq!(storage::get(b"key").unwrap(), Some(b"value".to_vec()));
+//!
+//! mock::set_read_only(true);
+//! assert!(storage::put(b"key", b"other").is_err());
+//! ```
+//!
+//! A result longer than the return buffer is read in several calls, and the host function only
+//! runs on the first one, so the values taken or popped are returned whole:
+//!
+//! ```
+//! use vrs_core_sdk::{mock, storage};
+//!
+//! const BLOBS: storage::Queue<Vec<u8>> = storage::Queue::new(b"blobs:");
+//!
+//! mock::reset();
+//! let value = vec![7u8; 4096];
+//! storage::put(b"key", &value).unwrap();
+//! assert_eq!(storage::take(b"key").unwrap(), Some(value.clone()));
+//! assert_eq!(storage::take(b"key").unwrap(), None);
+//!
+//! BLOBS.push(&value).unwrap();
+//! assert_eq!(BLOBS.pop().unwrap(), Some(value));
+//! assert_eq!(BLOBS.pop().unwrap(), None);
+//! ```
+
+use crate::{
+    error::RuntimeError,
+    storage::{proof, snapshot::Snapshot, Usage},
+    CallResult, NucleusId,
+};
+use codec::{Decode, Encode};
+use std::{cell::RefCell, collections::BTreeMap};
+
+thread_local! {
+    static STORE: RefCell<BTreeMap<Vec<u8>, Vec<u8>>> = const { RefCell::new(BTreeMap::new()) };
+    static READ_ONLY: RefCell<bool> = const { RefCell::new(false) };
+    static NOW: RefCell<u64> = const { RefCell::new(0) };
+    static NUCLEUS_ID: RefCell<[u8; 32]> = const { RefCell::new([0u8; 32]) };
+    // the store at the beginning of each open transaction
+    static TRANSACTIONS: RefCell<Vec<BTreeMap<Vec<u8>, Vec<u8>>>> = const { RefCell::new(Vec::new()) };
+    // the encoded result of the last host call, read again from an offset when it's long
+    static PENDING: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Clear the storage, leave the read-only mode and set the clock to `0`.
+pub fn reset() {
+    STORE.with(|s| s.borrow_mut().clear());
+    TRANSACTIONS.with(|t| t.borrow_mut().clear());
+    set_read_only(false);
+    set_now(0);
+}
+
+/// Set the timestamp returned by `timer::now`.
+pub fn set_now(now: u64) {
+    NOW.with(|n| *n.borrow_mut() = now);
+}
+
+/// Reject all writes with `RuntimeError::ReadOnly`, like the host does in `#[get]` functions.
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.with(|r| *r.borrow_mut() = read_only);
+}
+
+/// Set the id returned by `nucleus_id`.
+pub fn set_nucleus_id(id: NucleusId) {
+    NUCLEUS_ID.with(|n| *n.borrow_mut() = id.into());
+}
+
+/// Replace the storage and the nucleus id with the ones of `snapshot`.
+pub fn load(snapshot: &Snapshot) {
+    STORE.with(|s| *s.borrow_mut() = snapshot.entries().clone());
+    set_nucleus_id(snapshot.nucleus_id().clone());
+}
+
+/// Take a snapshot of the storage.
+pub fn snapshot() -> Snapshot {
+    let id = NUCLEUS_ID.with(|n| *n.borrow());
+    Snapshot::new(id.into(), entries())
+}
+
+/// All entries of the storage ordered by key.
+pub fn entries() -> Vec<(Vec<u8>, Vec<u8>)> {
+    STORE.with(|s| {
+        s.borrow()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    })
+}
+
+fn read<T>(f: impl FnOnce(&BTreeMap<Vec<u8>, Vec<u8>>) -> T) -> T {
+    STORE.with(|s| f(&s.borrow()))
+}
+
+fn write<T>(f: impl FnOnce(&mut BTreeMap<Vec<u8>, Vec<u8>>) -> CallResult<T>) -> CallResult<T> {
+    if READ_ONLY.with(|r| *r.borrow()) {
+        return Err(RuntimeError::ReadOnly);
+    }
+    STORE.with(|s| f(&mut s.borrow_mut()))
+}
+
+unsafe fn slice<'a>(ptr: *const u8, len: i32) -> &'a [u8] {
+    if len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(ptr, len as usize)
+}
+
+/// Write the part of the encoded result of `f` starting from `offset` and return its total length.
+///
+/// `f` only runs on the first call, at offset `0`. The calls reading the rest of a result longer
+/// than the buffer are served from the one encoded by it, so the host functions with side effects,
+/// like `storage_take`, aren't run again.
+unsafe fn write_result<T: Encode>(
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+    f: impl FnOnce() -> CallResult<T>,
+) -> i32 {
+    let encoded = if offset == 0 {
+        let encoded = f().encode();
+        PENDING.with(|p| p.borrow_mut().clone_from(&encoded));
+        encoded
+    } else {
+        PENDING.with(|p| p.borrow().clone())
+    };
+    let remaining = encoded.get(offset as usize..).unwrap_or_default();
+    let len = remaining.len().min(return_cap as usize);
+    std::ptr::copy_nonoverlapping(remaining.as_ptr(), return_ptr, len);
+    encoded.len() as i32
+}
+
+fn seek<'a>(
+    store: &'a BTreeMap<Vec<u8>, Vec<u8>>,
+    start: &[u8],
+    direction: i32,
+) -> Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a> {
+    let start = start.to_vec();
+    if direction == 0 {
+        Box::new(store.range(start..).map(|(k, v)| (k.clone(), v.clone())))
+    } else {
+        Box::new(
+            store
+                .range(..=start)
+                .rev()
+                .map(|(k, v)| (k.clone(), v.clone())),
+        )
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_put_v2(
+    key_ptr: *const u8,
+    key_len: i32,
+    value_ptr: *const u8,
+    value_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(key_ptr, key_len).to_vec();
+    let value = slice(value_ptr, value_len).to_vec();
+    write_result(return_ptr, return_cap, offset, || {
+        write(|s| {
+            s.insert(key, value);
+            Ok(())
+        })
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_del_v2(
+    key_ptr: *const u8,
+    key_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(key_ptr, key_len);
+    write_result(return_ptr, return_cap, offset, || {
+        write(|s| {
+            s.remove(key);
+            Ok(())
+        })
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_get_v2(
+    k_ptr: *const u8,
+    k_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| s.get(key).cloned()))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_get_prefix_v2(
+    k_ptr: *const u8,
+    k_len: i32,
+    direction: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| seek(s, key, direction).next()))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_get_range_v2(
+    k_ptr: *const u8,
+    k_len: i32,
+    direction: i32,
+    limit: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| {
+            seek(s, key, direction)
+                .take(limit as usize)
+                .collect::<Vec<_>>()
+        }))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_del_range_v2(
+    s0_ptr: *const u8,
+    s0_len: i32,
+    s1_ptr: *const u8,
+    s1_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let start = slice(s0_ptr, s0_len).to_vec();
+    let end = slice(s1_ptr, s1_len).to_vec();
+    write_result(return_ptr, return_cap, offset, || {
+        write(|s| {
+            if start < end {
+                let keys: Vec<_> = s.range(start..end).map(|(k, _)| k.clone()).collect();
+                for key in keys {
+                    s.remove(&key);
+                }
+            }
+            Ok(())
+        })
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_increment(
+    k_ptr: *const u8,
+    k_len: i32,
+    delta: i64,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        write(|s| {
+            let current = match s.get(key) {
+                Some(v) => i64::decode(&mut &v[..]).map_err(|_| {
+                    RuntimeError::KvStorageError("the value is not a counter".to_string())
+                })?,
+                None => 0,
+            };
+            let new = current
+                .checked_add(delta)
+                .ok_or_else(|| RuntimeError::KvStorageError("counter overflow".to_string()))?;
+            s.insert(key.to_vec(), new.encode());
+            Ok(new)
+        })
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_compare_and_swap(
+    k_ptr: *const u8,
+    k_len: i32,
+    args_ptr: *const u8,
+    args_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        write(|s| {
+            let (expected, new) =
+                <(Option<Vec<u8>>, Option<Vec<u8>>)>::decode(&mut slice(args_ptr, args_len))
+                    .map_err(|_| RuntimeError::DecodeReturnValueError)?;
+            if s.get(key) != expected.as_ref() {
+                return Ok(false);
+            }
+            match new {
+                Some(new) => s.insert(key.to_vec(), new),
+                None => s.remove(key),
+            };
+            Ok(true)
+        })
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_take(
+    k_ptr: *const u8,
+    k_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        write(|s| Ok(s.remove(key)))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_exists(
+    k_ptr: *const u8,
+    k_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| s.contains_key(key)))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_value_len(
+    k_ptr: *const u8,
+    k_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| s.get(key).map(|v| v.len() as u32)))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_get_slice(
+    k_ptr: *const u8,
+    k_len: i32,
+    value_offset: u32,
+    value_len: u32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| {
+            s.get(key).map(|v| {
+                let start = (value_offset as usize).min(v.len());
+                let end = start.saturating_add(value_len as usize).min(v.len());
+                v[start..end].to_vec()
+            })
+        }))
+    })
+}
+
+fn leaves(store: &BTreeMap<Vec<u8>, Vec<u8>>) -> Vec<proof::Hash> {
+    store.iter().map(|(k, v)| proof::entry_hash(k, v)).collect()
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_prove(
+    k_ptr: *const u8,
+    k_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let key = slice(k_ptr, k_len);
+    write_result(return_ptr, return_cap, offset, || {
+        read(|s| {
+            let index = s
+                .keys()
+                .position(|k| k == key)
+                .ok_or(RuntimeError::NotFound)?;
+            let leaves = leaves(s);
+            Ok(proof::Proof {
+                leaf_index: index as u64,
+                leaf_count: leaves.len() as u64,
+                path: proof::inclusion_path(&leaves, index),
+            })
+        })
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_state_root(return_ptr: *mut u8, return_cap: i32, offset: i32) -> i32 {
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| proof::root(&leaves(s))))
+    })
+}
+
+fn usage<'a>(entries: impl Iterator<Item = (&'a Vec<u8>, &'a Vec<u8>)>) -> Usage {
+    entries.fold(Usage::default(), |usage, (k, v)| Usage {
+        keys: usage.keys + 1,
+        bytes: usage.bytes + (k.len() + v.len()) as u64,
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_usage(return_ptr: *mut u8, return_cap: i32, offset: i32) -> i32 {
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| usage(s.iter())))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_prefix_usage(
+    p_ptr: *const u8,
+    p_len: i32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let prefix = slice(p_ptr, p_len);
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(read(|s| {
+            usage(
+                s.range(prefix.to_vec()..)
+                    .take_while(|(k, _)| k.starts_with(prefix)),
+            )
+        }))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_begin_transaction(
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    write_result(return_ptr, return_cap, offset, || {
+        let store = read(|s| s.clone());
+        TRANSACTIONS.with(|t| t.borrow_mut().push(store));
+        Ok(())
+    })
+}
+
+fn end_transaction(rollback: bool) -> CallResult<()> {
+    let saved = TRANSACTIONS
+        .with(|t| t.borrow_mut().pop())
+        .ok_or_else(|| RuntimeError::KvStorageError("no open transaction".to_string()))?;
+    if rollback {
+        STORE.with(|s| *s.borrow_mut() = saved);
+    }
+    Ok(())
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_commit_transaction(
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    write_result(return_ptr, return_cap, offset, || end_transaction(false))
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_rollback_transaction(
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    write_result(return_ptr, return_cap, offset, || end_transaction(true))
+}
+
+fn decode_print(ptr: *const u8, len: i32) -> String {
+    let s = unsafe { slice(ptr, len) };
+    String::decode(&mut &s[..]).unwrap_or_default()
+}
+
+#[no_mangle]
+extern "C" fn stdout_print(ptr: *const u8, len: i32) {
+    print!("{}", decode_print(ptr, len));
+}
+
+#[no_mangle]
+extern "C" fn stderr_print(ptr: *const u8, len: i32) {
+    eprint!("{}", decode_print(ptr, len));
+}
+
+#[no_mangle]
+extern "C" fn now_timestamp() -> u64 {
+    NOW.with(|n| *n.borrow())
+}
+
+#[no_mangle]
+unsafe extern "C" fn storage_sealing_key(
+    ns_ptr: *const u8,
+    ns_len: i32,
+    version: u32,
+    return_ptr: *mut u8,
+    return_cap: i32,
+    offset: i32,
+) -> i32 {
+    let namespace = slice(ns_ptr, ns_len);
+    let seed = [
+        b"vrs:mock:sealing_key".as_ref(),
+        namespace,
+        &version.to_le_bytes(),
+    ]
+    .concat();
+    write_result(return_ptr, return_cap, offset, || {
+        Ok(sp_crypto_hashing::blake2_256(&seed))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn get_nucleus_id(ptr: *mut u8) {
+    let id = NUCLEUS_ID.with(|n| *n.borrow());
+    std::ptr::copy_nonoverlapping(id.as_ptr(), ptr, id.len());
+}
@@ -4,10 +4,234 @@
 //! submit to Verisense chain.
 //!
 //! The `put` and `del` can only be called in the post functions. Otherwise, it will case panic.
+//!
+//! The host functions behind [`increment`], [`compare_and_swap`], [`take`], [`exists`],
+//! [`value_len`], [`get_slice`], [`prove`], [`state_root`], [`usage`], [`prefix_usage`] and
+//! [`transaction`] are only provided by the length-aware host. With the `legacy-host` feature
+//! they fail with `RuntimeError::UnexpectedHostStatus(HOST_FN_UNSUPPORTED)`, and so do the
+//! helpers built on them.
+//!
+//! # Entities
+//!
+//! `#[derive(Entity)]` generates a `<Name>Repository` storing the struct by its
+//! `#[primary_key]` and maintaining an index for each `#[index]` field. Entities declared in a
+//! `#[nucleus]` module are registered in the ABI, so they should also derive `TypeInfo`.
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::{Cursor, Page};
+//! use vrs_core_sdk::{get, post, CallResult, Entity};
+//!
+//! #[derive(Debug, Decode, Encode, Entity)]
+//! #[codec(crate = vrs_core_sdk::codec)]
+//! pub struct Task {
+//!     #[primary_key]
+//!     pub id: String,
+//!     #[index]
+//!     pub context_id: String,
+//!     #[index(unique)]
+//!     pub title: String,
+//! }
+//!
+//! #[post]
+//! pub fn add_task(task: Task) -> Result<(), String> {
+//!     TaskRepository::insert(&task).map_err(|e| e.to_string())
+//! }
+//!
+//! #[get]
+//! pub fn tasks_of_context(context_id: String) -> Result<Vec<Task>, String> {
+//!     TaskRepository::find_by_context_id(&context_id, 100).map_err(|e| e.to_string())
+//! }
+//!
+//! #[get]
+//! pub fn task_by_title(title: String) -> CallResult<Option<Task>> {
+//!     TaskRepository::find_by_title(&title)
+//! }
+//!
+//! #[get]
+//! pub fn list_tasks(cursor: Option<Cursor>) -> CallResult<Page<Task>> {
+//!     TaskRepository::list(cursor.as_ref(), 20)
+//! }
+//! ```
+
+pub mod blob;
+pub mod compress;
+pub mod event_store;
+pub mod idempotency;
+mod index;
+pub mod key;
+mod map;
+pub mod merkle_log;
+pub mod migration;
+mod page;
+pub mod proof;
+pub mod queue;
+mod quota;
+pub mod sealed;
+mod sequence;
+#[cfg(feature = "std")]
+pub mod snapshot;
+pub mod sorted_set;
+pub mod ttl;
+
+pub use event_store::EventStore;
+pub use index::{Index, Indexer, UniqueIndex};
+pub use map::StorageMap;
+pub use merkle_log::MerkleLog;
+pub use page::{list_page, Cursor, Page};
+pub use proof::{verify_proof, Proof};
+pub use queue::{Deque, Queue};
+pub use quota::{Quota, Usage};
+pub use sequence::Sequence;
+pub use sorted_set::SortedSet;
 
 use crate::{error::RuntimeError, CallResult};
-use codec::Decode;
+use codec::{Decode, Encode};
+
+/// the maximum number of entries returned by `get_range`
+pub const MAX_RANGE_LIMIT: usize = 1000;
+
+#[cfg(not(feature = "legacy-host"))]
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn storage_put_v2(
+        key_ptr: *const u8,
+        key_len: i32,
+        value_ptr: *const u8,
+        value_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_del_v2(
+        key_ptr: *const u8,
+        key_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_get_v2(
+        k_ptr: *const u8,
+        k_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_get_prefix_v2(
+        k_ptr: *const u8,
+        k_len: i32,
+        direction: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_get_range_v2(
+        k_ptr: *const u8,
+        k_len: i32,
+        direction: i32,
+        limit: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_del_range_v2(
+        s0_ptr: *const u8,
+        s0_len: i32,
+        s1_ptr: *const u8,
+        s1_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+}
+
+length_aware_host_fns! {
+    fn storage_increment(
+        k_ptr: *const u8,
+        k_len: i32,
+        delta: i64,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_compare_and_swap(
+        k_ptr: *const u8,
+        k_len: i32,
+        args_ptr: *const u8,
+        args_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_take(
+        k_ptr: *const u8,
+        k_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_exists(
+        k_ptr: *const u8,
+        k_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_value_len(
+        k_ptr: *const u8,
+        k_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_get_slice(
+        k_ptr: *const u8,
+        k_len: i32,
+        value_offset: u32,
+        value_len: u32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_prove(
+        k_ptr: *const u8,
+        k_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_state_root(return_ptr: *mut u8, return_cap: i32, offset: i32) -> i32;
+
+    fn storage_usage(return_ptr: *mut u8, return_cap: i32, offset: i32) -> i32;
+
+    fn storage_prefix_usage(
+        p_ptr: *const u8,
+        p_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+
+    fn storage_begin_transaction(return_ptr: *mut u8, return_cap: i32, offset: i32) -> i32;
+
+    fn storage_commit_transaction(return_ptr: *mut u8, return_cap: i32, offset: i32) -> i32;
+
+    fn storage_rollback_transaction(return_ptr: *mut u8, return_cap: i32, offset: i32) -> i32;
+}
 
+#[cfg(feature = "legacy-host")]
 #[link(wasm_import_module = "env")]
 extern "C" {
     fn storage_put(
@@ -52,54 +276,219 @@ extern "C" {
 pub fn put(key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> CallResult<()> {
     let key = key.as_ref();
     let value = value.as_ref();
-    assert!(key.len() <= i32::MAX as usize);
-    assert!(value.len() <= i32::MAX as usize);
-    let mut buf = crate::allocate_buffer();
-    let status = unsafe {
-        storage_put(
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let value_len = crate::check_len(value, RuntimeError::ValueTooLarge)?;
+    #[cfg(not(feature = "legacy-host"))]
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_put_v2(
             key.as_ptr(),
-            key.len() as i32,
+            key_len,
             value.as_ptr(),
-            value.len() as i32,
-            buf.as_mut_ptr(),
+            value_len,
+            ptr,
+            cap,
+            offset,
         )
-    };
-    assert!(status == crate::NO_MORE_DATA);
-    CallResult::<()>::decode(&mut &buf[..]).map_err(|_| RuntimeError::DecodeReturnValueError)?
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let buf = crate::read_legacy_buffer(|ptr| unsafe {
+        storage_put(key.as_ptr(), key_len, value.as_ptr(), value_len, ptr)
+    })?;
+    crate::decode_host_result(&buf)
 }
 
 /// Delete a key-value pair from the kvdb.
 pub fn del(key: impl AsRef<[u8]>) -> CallResult<()> {
     let key = key.as_ref();
-    assert!(key.len() <= i32::MAX as usize);
-    let mut buf = crate::allocate_buffer();
-    let status = unsafe { storage_del(key.as_ptr(), key.len() as i32, buf.as_mut_ptr()) };
-    assert!(status == crate::NO_MORE_DATA);
-    CallResult::<()>::decode(&mut &buf[..]).map_err(|_| RuntimeError::DecodeReturnValueError)?
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    #[cfg(not(feature = "legacy-host"))]
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_del_v2(key.as_ptr(), key_len, ptr, cap, offset)
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let buf = crate::read_legacy_buffer(|ptr| unsafe { storage_del(key.as_ptr(), key_len, ptr) })?;
+    crate::decode_host_result(&buf)
 }
 
 /// Get a value from the kvdb with the key.
 pub fn get(key: impl AsRef<[u8]>) -> CallResult<Option<Vec<u8>>> {
     let key = key.as_ref();
-    assert!(key.len() <= i32::MAX as usize);
-    let mut buf = crate::allocate_buffer();
-    let mut val = vec![];
-    loop {
-        let status = unsafe {
-            storage_get(
-                key.as_ptr(),
-                key.len() as i32,
-                buf.as_mut_ptr(),
-                val.len() as i32,
-            )
-        };
-        val.extend_from_slice(&buf);
-        if status == crate::NO_MORE_DATA {
-            break;
-        }
-    }
-    CallResult::<Option<Vec<u8>>>::decode(&mut &val[..])
-        .map_err(|_| RuntimeError::DecodeReturnValueError)?
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    #[cfg(not(feature = "legacy-host"))]
+    let val = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_get_v2(key.as_ptr(), key_len, ptr, cap, offset)
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let val = crate::read_legacy_pages(|ptr, offset| unsafe {
+        storage_get(key.as_ptr(), key_len, ptr, offset)
+    })?;
+    crate::decode_host_result(&val)
+}
+
+/// Atomically add `delta` to the counter stored under `key` and return the new value.
+///
+/// The counter is stored as a SCALE encoded `i64` and starts from `0` if the key is absent.
+/// The host rejects the operation if the stored value isn't an `i64` or the result overflows.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "mock")]
+/// # {
+/// use vrs_core_sdk::{mock, storage};
+///
+/// mock::reset();
+/// assert_eq!(storage::increment(b"counter", 2).unwrap(), 2);
+/// assert_eq!(storage::increment(b"counter", -1).unwrap(), 1);
+/// # }
+/// ```
+pub fn increment(key: impl AsRef<[u8]>, delta: i64) -> CallResult<i64> {
+    let key = key.as_ref();
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_increment(key.as_ptr(), key_len, delta, ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Atomically replace the value of `key` with `new` if the current value equals `expected`,
+/// returning whether the value was replaced.
+///
+/// `expected = None` requires the key to be absent and `new = None` deletes the key.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "mock")]
+/// # {
+/// use vrs_core_sdk::{mock, storage};
+///
+/// mock::reset();
+/// assert!(storage::compare_and_swap(b"owner", None, Some(b"alice")).unwrap());
+/// assert!(!storage::compare_and_swap(b"owner", None, Some(b"bob")).unwrap());
+/// assert_eq!(storage::take(b"owner").unwrap(), Some(b"alice".to_vec()));
+/// assert_eq!(storage::get(b"owner").unwrap(), None);
+/// # }
+/// ```
+pub fn compare_and_swap(
+    key: impl AsRef<[u8]>,
+    expected: Option<&[u8]>,
+    new: Option<&[u8]>,
+) -> CallResult<bool> {
+    let key = key.as_ref();
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let args = (expected, new).encode();
+    let args_len = crate::check_len(&args, RuntimeError::ValueTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_compare_and_swap(
+            key.as_ptr(),
+            key_len,
+            args.as_ptr(),
+            args_len,
+            ptr,
+            cap,
+            offset,
+        )
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Atomically delete `key` and return its value.
+pub fn take(key: impl AsRef<[u8]>) -> CallResult<Option<Vec<u8>>> {
+    let key = key.as_ref();
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_take(key.as_ptr(), key_len, ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Check whether `key` exists in the kvdb without reading its value.
+pub fn exists(key: impl AsRef<[u8]>) -> CallResult<bool> {
+    let key = key.as_ref();
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_exists(key.as_ptr(), key_len, ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Get the length in bytes of the value of `key` without reading it, `None` if the key is absent.
+pub fn value_len(key: impl AsRef<[u8]>) -> CallResult<Option<u32>> {
+    let key = key.as_ref();
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_value_len(key.as_ptr(), key_len, ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Get at most `len` bytes of the value of `key` starting from `offset`, `None` if the key is
+/// absent.
+///
+/// The slice is truncated at the end of the value, so it is empty if `offset` is beyond the end.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "mock")]
+/// # {
+/// use vrs_core_sdk::{mock, storage};
+///
+/// mock::reset();
+/// storage::put(b"greeting", b"hello world").unwrap();
+/// assert!(storage::exists(b"greeting").unwrap());
+/// assert_eq!(storage::value_len(b"greeting").unwrap(), Some(11));
+/// assert_eq!(storage::get_slice(b"greeting", 6, 100).unwrap(), Some(b"world".to_vec()));
+/// assert_eq!(storage::get_slice(b"greeting", 20, 1).unwrap(), Some(vec![]));
+/// assert_eq!(storage::value_len(b"none").unwrap(), None);
+/// # }
+/// ```
+pub fn get_slice(key: impl AsRef<[u8]>, offset: u32, len: u32) -> CallResult<Option<Vec<u8>>> {
+    let key = key.as_ref();
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, return_offset| unsafe {
+        storage_get_slice(key.as_ptr(), key_len, offset, len, ptr, cap, return_offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Get the merkle proof of `key` against the current [`state_root`], `RuntimeError::NotFound`
+/// if the key is absent. See [`proof`] for the verification.
+pub fn prove(key: impl AsRef<[u8]>) -> CallResult<Proof> {
+    let key = key.as_ref();
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_prove(key.as_ptr(), key_len, ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Get the root of the merkle tree of the kv storage defined in [`proof`].
+///
+/// It isn't the state root submitted to the Verisense chain, see [`proof`].
+pub fn state_root() -> CallResult<proof::Hash> {
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_state_root(ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Get the number of keys and the total bytes held by the kv storage of the nucleus.
+pub fn usage() -> CallResult<Usage> {
+    let buf =
+        crate::read_host_buffer(|ptr, cap, offset| unsafe { storage_usage(ptr, cap, offset) })?;
+    crate::decode_host_result(&buf)
+}
+
+/// Get the number of keys and the total bytes of the entries starting with `prefix`.
+pub fn prefix_usage(prefix: impl AsRef<[u8]>) -> CallResult<Usage> {
+    let prefix = prefix.as_ref();
+    let prefix_len = crate::check_len(prefix, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_prefix_usage(prefix.as_ptr(), prefix_len, ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
 }
 
 /// The direction of the search
@@ -128,35 +517,41 @@ impl From<i32> for Direction {
     }
 }
 
-/// Get a batch of entries from the databass with "start_key" and direction , the limit maximum is 1000
+/// Get a batch of entries from the databass with "start_key" and direction , the limit maximum is `MAX_RANGE_LIMIT`
 pub fn get_range(
     start_key: impl AsRef<[u8]>,
     direction: Direction,
     limit: usize,
 ) -> CallResult<Vec<(Vec<u8>, Vec<u8>)>> {
     let start = start_key.as_ref();
-    assert!(start.len() <= i32::MAX as usize);
-    assert!(limit <= 1000);
-    let mut buf = crate::allocate_buffer();
-    let mut val = vec![];
-    loop {
-        let status = unsafe {
-            storage_get_range(
-                start.as_ptr(),
-                start.len() as i32,
-                direction.into(),
-                limit as i32,
-                buf.as_mut_ptr(),
-                val.len() as i32,
-            )
-        };
-        val.extend_from_slice(&buf);
-        if status == crate::NO_MORE_DATA {
-            break;
-        }
+    let start_len = crate::check_len(start, RuntimeError::KeyTooLarge)?;
+    if limit > MAX_RANGE_LIMIT {
+        return Err(RuntimeError::LimitExceeded);
     }
-    CallResult::<Vec<(Vec<u8>, Vec<u8>)>>::decode(&mut &val[..])
-        .map_err(|_| RuntimeError::DecodeReturnValueError)?
+    #[cfg(not(feature = "legacy-host"))]
+    let val = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_get_range_v2(
+            start.as_ptr(),
+            start_len,
+            direction.into(),
+            limit as i32,
+            ptr,
+            cap,
+            offset,
+        )
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let val = crate::read_legacy_pages(|ptr, offset| unsafe {
+        storage_get_range(
+            start.as_ptr(),
+            start_len,
+            direction.into(),
+            limit as i32,
+            ptr,
+            offset,
+        )
+    })?;
+    crate::decode_host_result(&val)
 }
 
 /// Search a key-value with a prefix and direction
@@ -180,44 +575,223 @@ pub fn search(
     direction: Direction,
 ) -> CallResult<Option<(Vec<u8>, Vec<u8>)>> {
     let key = key_prefix.as_ref();
-    assert!(key.len() <= i32::MAX as usize);
-    let mut buf = crate::allocate_buffer();
-    let mut val = vec![];
-    loop {
-        let status = unsafe {
-            storage_get_prefix(
-                key.as_ptr(),
-                key.len() as i32,
-                direction.into(),
-                buf.as_mut_ptr(),
-                val.len() as i32,
-            )
-        };
-        val.extend_from_slice(&buf);
-        if status == crate::NO_MORE_DATA {
-            break;
-        }
-    }
-    CallResult::<Option<(Vec<u8>, Vec<u8>)>>::decode(&mut &val[..])
-        .map_err(|_| RuntimeError::DecodeReturnValueError)?
+    let key_len = crate::check_len(key, RuntimeError::KeyTooLarge)?;
+    #[cfg(not(feature = "legacy-host"))]
+    let val = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_get_prefix_v2(key.as_ptr(), key_len, direction.into(), ptr, cap, offset)
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let val = crate::read_legacy_pages(|ptr, offset| unsafe {
+        storage_get_prefix(key.as_ptr(), key_len, direction.into(), ptr, offset)
+    })?;
+    crate::decode_host_result(&val)
 }
 
 /// Removes the database entries in the range [start_key, end_key)
+///
+/// All entries starting with a prefix are in the range `[prefix, prefix_successor(prefix))`,
+/// see [`clear_prefix`] to remove them in bounded batches.
 pub fn delete_range(start_key: impl AsRef<[u8]>, end_key: impl AsRef<[u8]>) -> CallResult<()> {
     let start = start_key.as_ref();
     let end = end_key.as_ref();
-    assert!(start.len() <= i32::MAX as usize);
-    assert!(end.len() <= i32::MAX as usize);
-    let mut buf = crate::allocate_buffer();
-    let status = unsafe {
-        storage_del_range(
+    let start_len = crate::check_len(start, RuntimeError::KeyTooLarge)?;
+    let end_len = crate::check_len(end, RuntimeError::KeyTooLarge)?;
+    #[cfg(not(feature = "legacy-host"))]
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_del_range_v2(
             start.as_ptr(),
-            start.len() as i32,
+            start_len,
             end.as_ptr(),
-            end.len() as i32,
-            buf.as_mut_ptr(),
+            end_len,
+            ptr,
+            cap,
+            offset,
         )
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let buf = crate::read_legacy_buffer(|ptr| unsafe {
+        storage_del_range(start.as_ptr(), start_len, end.as_ptr(), end_len, ptr)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+/// The result of [`clear_prefix`].
+#[derive(Clone, Debug, Decode, Encode, Eq, PartialEq)]
+pub struct ClearResult {
+    /// the number of entries removed by this call
+    pub removed: u32,
+    /// the first remaining key under the prefix, `None` if the prefix has been cleared
+    pub cursor: Option<Vec<u8>>,
+}
+
+/// Remove at most `limit` entries starting with `prefix`, the limit maximum is
+/// `MAX_RANGE_LIMIT`.
+///
+/// Call it again with the same prefix while the returned cursor is `Some` to remove the rest,
+/// e.g. one batch per post call to bound the cost of each call.
+///
+/// # Examples
+///
+/// ```
+/// use vrs_core_sdk::{post, storage};
+///
+/// #[post]
+/// pub fn clear_comments(blog_id: u64) -> Result<bool, String> {
+///     let prefix = [&b"comment:"[..], &blog_id.to_be_bytes()[..]].concat();
+///     let result = storage::clear_prefix(&prefix, 100).map_err(|e| e.to_string())?;
+///     Ok(result.cursor.is_none())
+/// }
+/// ```
+///
+/// With the `mock` feature:
+///
+/// ```
+/// # #[cfg(feature = "mock")]
+/// # {
+/// use vrs_core_sdk::{mock, storage};
+///
+/// mock::reset();
+/// storage::put(b"user:1", b"alice").unwrap();
+/// storage::put(b"user:2", b"bob").unwrap();
+/// let result = storage::clear_prefix(b"user:", 1).unwrap();
+/// assert_eq!(result.removed, 1);
+/// assert_eq!(result.cursor, Some(b"user:2".to_vec()));
+/// assert_eq!(storage::clear_prefix(b"user:", 1).unwrap().cursor, None);
+/// assert_eq!(storage::get(b"user:2").unwrap(), None);
+/// # }
+/// ```
+pub fn clear_prefix(prefix: impl AsRef<[u8]>, limit: usize) -> CallResult<ClearResult> {
+    let prefix = prefix.as_ref();
+    let keys = get_range(prefix, Direction::Forward, limit)?
+        .into_iter()
+        .map(|(k, _)| k)
+        .take_while(|k| k.starts_with(prefix))
+        .collect::<Vec<_>>();
+    if let (Some(first), Some(last)) = (keys.first(), keys.last()) {
+        // the keys are consecutive, so `[first, last ++ 0x00)` holds exactly these keys
+        let end = [&last[..], &[0u8][..]].concat();
+        delete_range(first, end)?;
+    }
+    let cursor = get_range(prefix, Direction::Forward, 1)?
+        .into_iter()
+        .map(|(k, _)| k)
+        .find(|k| k.starts_with(prefix));
+    Ok(ClearResult {
+        removed: keys.len() as u32,
+        cursor,
+    })
+}
+
+/// Run `f` in a transaction of the kvdb, its changes are applied if it returns `Ok` and
+/// discarded if it returns `Err`. Transactions can be nested.
+///
+/// # Examples
+///
+/// ```
+/// use vrs_core_sdk::{post, storage};
+///
+/// #[post]
+/// pub fn transfer(from: String, to: String, amount: i64) -> Result<(), String> {
+///     storage::transaction(|| {
+///         storage::increment(format!("balance:{}", to), amount).map_err(|e| e.to_string())?;
+///         let rest = storage::increment(format!("balance:{}", from), -amount)
+///             .map_err(|e| e.to_string())?;
+///         if rest < 0 {
+///             // the deposit above is discarded
+///             return Err("insufficient balance".to_string());
+///         }
+///         Ok(())
+///     })
+///     .map_err(|e| e.to_string())?
+/// }
+/// ```
+pub fn transaction<T, E>(f: impl FnOnce() -> Result<T, E>) -> CallResult<Result<T, E>> {
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_begin_transaction(ptr, cap, offset)
+    })?;
+    crate::decode_host_result::<()>(&buf)?;
+    let result = f();
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        if result.is_ok() {
+            storage_commit_transaction(ptr, cap, offset)
+        } else {
+            storage_rollback_transaction(ptr, cap, offset)
+        }
+    })?;
+    crate::decode_host_result::<()>(&buf)?;
+    Ok(result)
+}
+
+/// Decode a value read from the kvdb, the key is reported if the value is malformed.
+pub(crate) fn decode_value<T: Decode>(key: &[u8], value: &[u8]) -> CallResult<T> {
+    T::decode(&mut &value[..]).map_err(|_| {
+        RuntimeError::KvStorageError(format!(
+            "failed to decode the value of 0x{}",
+            sp_core::hexdisplay::HexDisplay::from(&key)
+        ))
+    })
+}
+
+/// The smallest key which is greater than all keys starting with `prefix`, `None` if there is
+/// no such key, i.e. the prefix is empty or consists of `0xff` only.
+///
+/// This is the exclusive end key for removing a prefix with [`delete_range`].
+///
+/// ```
+/// use vrs_core_sdk::storage::prefix_successor;
+///
+/// assert_eq!(prefix_successor(b"user:"), Some(b"user;".to_vec()));
+/// assert_eq!(prefix_successor(&[0x01, 0xff]), Some(vec![0x02]));
+/// assert_eq!(prefix_successor(&[0xff]), None);
+/// ```
+pub fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut successor = prefix.to_vec();
+    while let Some(last) = successor.pop() {
+        if last < u8::MAX {
+            successor.push(last + 1);
+            return Some(successor);
+        }
+    }
+    None
+}
+
+/// The key to start from when reading all entries under `prefix` in `direction`.
+///
+/// A reverse read starts from the successor of the prefix. A prefix made of `0xff` bytes has
+/// none and its keys can be arbitrarily long, so no start key covers them all and the read is
+/// rejected rather than missing entries.
+pub(crate) fn prefix_start(prefix: &[u8], direction: Direction) -> CallResult<Vec<u8>> {
+    match direction {
+        Direction::Forward => Ok(prefix.to_vec()),
+        Direction::Reverse => prefix_successor(prefix).ok_or_else(|| {
+            RuntimeError::KvStorageError(format!(
+                "can't read in reverse under the prefix 0x{} which has no successor",
+                sp_core::hexdisplay::HexDisplay::from(&prefix)
+            ))
+        }),
+    }
+}
+
+/// Read at most `limit` entries under `prefix` starting from `start` in `direction`.
+///
+/// Entries before the first key under `prefix` are skipped, e.g. when reading in reverse from
+/// the successor of the prefix, and so is `start` itself if `exclusive` is set.
+pub(crate) fn scan_prefix(
+    prefix: &[u8],
+    start: &[u8],
+    exclusive: bool,
+    direction: Direction,
+    limit: usize,
+) -> CallResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    let fetch = if exclusive || !start.starts_with(prefix) {
+        (limit + 1).min(MAX_RANGE_LIMIT)
+    } else {
+        limit
     };
-    assert!(status == crate::NO_MORE_DATA);
-    CallResult::<()>::decode(&mut &buf[..]).map_err(|_| RuntimeError::DecodeReturnValueError)?
+    Ok(get_range(start, direction, fetch)?
+        .into_iter()
+        .skip_while(|(k, _)| (exclusive && k == start) || !k.starts_with(prefix))
+        .take_while(|(k, _)| k.starts_with(prefix))
+        .take(limit)
+        .collect())
 }
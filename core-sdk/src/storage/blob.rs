@@ -0,0 +1,216 @@
+//! Content-addressed storage for values too large to be handled as one value.
+//!
+//! A blob is split into chunks of [`CHUNK_SIZE`] bytes stored under the blake2-256 hash of the
+//! whole content, along with a [`Manifest`] describing it. Storing identical content again
+//! returns the same hash and only increases the reference count of the manifest, the chunks are
+//! removed once every reference has been released with [`remove`].
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::storage::blob::{self, BlobHash};
+//! use vrs_core_sdk::{get, post, CallResult};
+//!
+//! #[post]
+//! pub fn upload(data: Vec<u8>) -> CallResult<BlobHash> {
+//!     blob::put(&data)
+//! }
+//!
+//! #[get]
+//! pub fn download(hash: BlobHash, offset: u64, len: u64) -> CallResult<Option<Vec<u8>>> {
+//!     blob::read(&hash, offset, len)
+//! }
+//! ```
+//!
+//! With the `mock` feature, the chunks are removed along with the last reference:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::{mock, storage::blob};
+//!
+//! mock::reset();
+//! let data = vec![7u8; blob::CHUNK_SIZE + 10];
+//! let hash = blob::put(&data).unwrap();
+//! assert_eq!(blob::put(&data).unwrap(), hash);
+//! assert_eq!(blob::manifest(&hash).unwrap().unwrap().refs, 2);
+//! assert_eq!(blob::read(&hash, data.len() as u64 - 12, 100).unwrap(), Some(vec![7; 12]));
+//! assert_eq!(blob::get_all(&hash).unwrap(), Some(data));
+//! assert!(blob::remove(&hash).unwrap());
+//! assert!(blob::remove(&hash).unwrap());
+//! assert!(blob::manifest(&hash).unwrap().is_none());
+//! assert!(mock::entries().iter().all(|(k, _)| !k.starts_with(b"__vrs:blob:")));
+//! # }
+//! ```
+
+use super::{decode_value, del, delete_range, get, get_slice};
+use crate::{error::RuntimeError, CallResult};
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+
+/// the size of each chunk, the last chunk of a blob may be shorter
+pub const CHUNK_SIZE: usize = 32 * 1024;
+
+const MANIFEST_PREFIX: &[u8] = b"__vrs:blob:m:";
+const CHUNK_PREFIX: &[u8] = b"__vrs:blob:c:";
+
+/// The blake2-256 hash of the content of a blob.
+pub type BlobHash = [u8; 32];
+
+/// The description of a stored blob.
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, TypeInfo)]
+pub struct Manifest {
+    /// the length of the content in bytes
+    pub len: u64,
+    /// the size of each chunk when the blob was stored
+    pub chunk_size: u32,
+    /// the number of times the content has been stored and not removed
+    pub refs: u32,
+}
+
+impl Manifest {
+    /// The number of chunks of the blob.
+    pub fn chunks(&self) -> u32 {
+        self.len.div_ceil(self.chunk_size as u64) as u32
+    }
+}
+
+fn manifest_key(hash: &BlobHash) -> Vec<u8> {
+    [MANIFEST_PREFIX, &hash[..]].concat()
+}
+
+fn chunk_key(hash: &BlobHash, index: u32) -> Vec<u8> {
+    [CHUNK_PREFIX, &hash[..], &index.to_be_bytes()[..]].concat()
+}
+
+fn missing_chunk(hash: &BlobHash, index: u32) -> RuntimeError {
+    RuntimeError::KvStorageError(format!(
+        "chunk {} of blob 0x{} is missing",
+        index,
+        sp_core::hexdisplay::HexDisplay::from(hash)
+    ))
+}
+
+/// Store `data` and return its hash, the chunks are only written if the content is new.
+pub fn put(data: &[u8]) -> CallResult<BlobHash> {
+    let hash = sp_crypto_hashing::blake2_256(data);
+    let key = manifest_key(&hash);
+    let manifest = match manifest(&hash)? {
+        Some(manifest) => Manifest {
+            refs: manifest.refs.saturating_add(1),
+            ..manifest
+        },
+        None => {
+            for (index, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+                super::put(chunk_key(&hash, index as u32), chunk)?;
+            }
+            Manifest {
+                len: data.len() as u64,
+                chunk_size: CHUNK_SIZE as u32,
+                refs: 1,
+            }
+        }
+    };
+    super::put(key, manifest.encode())?;
+    Ok(hash)
+}
+
+/// Get the manifest of a blob, `None` if the blob doesn't exist.
+pub fn manifest(hash: &BlobHash) -> CallResult<Option<Manifest>> {
+    let key = manifest_key(hash);
+    get(&key)?.map(|v| decode_value(&key, &v)).transpose()
+}
+
+/// Get the whole content of a blob.
+pub fn get_all(hash: &BlobHash) -> CallResult<Option<Vec<u8>>> {
+    match manifest(hash)? {
+        Some(manifest) => read(hash, 0, manifest.len),
+        None => Ok(None),
+    }
+}
+
+/// Read at most `len` bytes of a blob starting from `offset`, only the chunks overlapping the
+/// range are read.
+///
+/// The result is truncated at the end of the blob, so it is empty if `offset` is beyond the end.
+pub fn read(hash: &BlobHash, offset: u64, len: u64) -> CallResult<Option<Vec<u8>>> {
+    let Some(manifest) = manifest(hash)? else {
+        return Ok(None);
+    };
+    let chunk_size = manifest.chunk_size as u64;
+    let end = offset.saturating_add(len).min(manifest.len);
+    let mut data = Vec::with_capacity(end.saturating_sub(offset) as usize);
+    let mut pos = offset;
+    while pos < end {
+        let index = (pos / chunk_size) as u32;
+        let within = pos % chunk_size;
+        let take = (chunk_size - within).min(end - pos);
+        let chunk = get_slice(chunk_key(hash, index), within as u32, take as u32)?
+            .filter(|chunk| chunk.len() as u64 == take)
+            .ok_or_else(|| missing_chunk(hash, index))?;
+        data.extend_from_slice(&chunk);
+        pos += take;
+    }
+    Ok(Some(data))
+}
+
+/// Iterate over the chunks of a blob in order, reading one chunk per step.
+pub fn chunks(hash: &BlobHash) -> CallResult<Option<Chunks>> {
+    Ok(manifest(hash)?.map(|manifest| Chunks {
+        hash: *hash,
+        next: 0,
+        count: manifest.chunks(),
+    }))
+}
+
+/// The iterator returned by [`chunks`].
+#[derive(Debug, Clone)]
+pub struct Chunks {
+    hash: BlobHash,
+    next: u32,
+    count: u32,
+}
+
+impl Iterator for Chunks {
+    type Item = CallResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.count {
+            return None;
+        }
+        let index = self.next;
+        self.next += 1;
+        Some(
+            get(chunk_key(&self.hash, index))
+                .and_then(|chunk| chunk.ok_or_else(|| missing_chunk(&self.hash, index))),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.count - self.next) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+/// Release one reference to a blob, the blob is removed with its chunks when no reference is
+/// left. Returns `false` if the blob doesn't exist.
+pub fn remove(hash: &BlobHash) -> CallResult<bool> {
+    let Some(manifest) = manifest(hash)? else {
+        return Ok(false);
+    };
+    let key = manifest_key(hash);
+    if manifest.refs > 1 {
+        super::put(
+            &key,
+            Manifest {
+                refs: manifest.refs - 1,
+                ..manifest
+            }
+            .encode(),
+        )?;
+    } else {
+        delete_range(chunk_key(hash, 0), chunk_key(hash, manifest.chunks()))?;
+        del(&key)?;
+    }
+    Ok(true)
+}
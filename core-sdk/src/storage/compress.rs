@@ -0,0 +1,126 @@
+//! Opt-in compression of the values of typed containers.
+//!
+//! A container created with a [`Compression`] stores each value behind a header byte, `0x00`
+//! for a plain SCALE encoded value and `0x01` for an LZ4 block. Only the values whose encoding
+//! is at least as long as the threshold are compressed, and only when it makes them shorter, so
+//! both forms coexist in the same container and the threshold can be changed at any time.
+//!
+//! The header changes the stored bytes, so the values of an existing container have to be
+//! migrated, e.g. with [`migration::translate`](super::migration::translate), when the
+//! compression is turned on or off.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::{compress::Compression, StorageMap};
+//!
+//! #[derive(Debug, Decode, Encode)]
+//! #[codec(crate = vrs_core_sdk::codec)]
+//! pub struct Message {
+//!     pub parts: Vec<String>,
+//! }
+//!
+//! const MESSAGES: StorageMap<u64, Message> =
+//!     StorageMap::new(b"message:").with_compression(Compression::lz4(256));
+//!
+//! let compression = Compression::lz4(16);
+//! let value = "hello ".repeat(100).into_bytes();
+//! let stored = compression.compress(&value);
+//! assert!(stored.len() < value.len());
+//! assert_eq!(Compression::decompress(&stored).unwrap(), value);
+//! ```
+//!
+//! With the `mock` feature, only the long values of a map are stored compressed:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::storage::{self, compress::Compression, StorageMap};
+//! use vrs_core_sdk::mock;
+//!
+//! const NOTES: StorageMap<u64, String> =
+//!     StorageMap::new(b"note:").with_compression(Compression::lz4(64));
+//!
+//! mock::reset();
+//! NOTES.insert(&1, &"a".repeat(1000)).unwrap();
+//! NOTES.insert(&2, &"b".to_string()).unwrap();
+//! assert!(storage::value_len(NOTES.storage_key(&1)).unwrap().unwrap() < 100);
+//! assert_eq!(storage::get(NOTES.storage_key(&2)).unwrap().unwrap()[0], 0x00);
+//! assert_eq!(NOTES.get(&1).unwrap(), Some("a".repeat(1000)));
+//! assert_eq!(NOTES.get(&2).unwrap(), Some("b".to_string()));
+//! # }
+//! ```
+
+use super::decode_value;
+use crate::{error::RuntimeError, CallResult};
+use codec::Decode;
+use std::borrow::Cow;
+
+const PLAIN: u8 = 0x00;
+const LZ4: u8 = 0x01;
+
+/// The compression of a typed container, see the [module](self) documentation.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Compression {
+    threshold: usize,
+}
+
+impl Compression {
+    /// Compress the values of at least `threshold` bytes with LZ4.
+    pub const fn lz4(threshold: usize) -> Self {
+        Self { threshold }
+    }
+
+    /// Prepend the header to `value`, compressing it if worthwhile.
+    pub fn compress(&self, value: &[u8]) -> Vec<u8> {
+        if value.len() >= self.threshold {
+            let compressed = lz4_flex::compress_prepend_size(value);
+            if compressed.len() < value.len() {
+                return [&[LZ4][..], &compressed].concat();
+            }
+        }
+        [&[PLAIN][..], value].concat()
+    }
+
+    /// Strip the header of a stored value, decompressing it if needed. `None` if the value is
+    /// malformed.
+    pub fn decompress(stored: &[u8]) -> Option<Cow<'_, [u8]>> {
+        match stored.split_first()? {
+            (&PLAIN, value) => Some(Cow::Borrowed(value)),
+            (&LZ4, compressed) => lz4_flex::decompress_size_prepended(compressed)
+                .ok()
+                .map(Cow::Owned),
+            _ => None,
+        }
+    }
+}
+
+/// Decode a value stored by a container with the optional `compression`.
+pub(crate) fn decode_stored<T: Decode>(
+    compression: Option<&Compression>,
+    key: &[u8],
+    stored: &[u8],
+) -> CallResult<T> {
+    if compression.is_none() {
+        return decode_value(key, stored);
+    }
+    let value = Compression::decompress(stored).ok_or_else(|| {
+        RuntimeError::KvStorageError(format!(
+            "failed to decompress the value of 0x{}",
+            sp_core::hexdisplay::HexDisplay::from(&key)
+        ))
+    })?;
+    decode_value(key, &value)
+}
+
+/// Encode a value to be stored by a container with the optional `compression`.
+pub(crate) fn encode_stored<T: codec::Encode>(
+    compression: Option<&Compression>,
+    value: &T,
+) -> Vec<u8> {
+    match compression {
+        Some(compression) => compression.compress(&value.encode()),
+        None => value.encode(),
+    }
+}
@@ -0,0 +1,251 @@
+//! Aggregates stored as streams of events.
+//!
+//! An [`EventStore`] appends the events of each stream with consecutive versions starting from
+//! `1`, and the state of a stream is the fold of its events from `S::default()`. Under the
+//! prefix of the store are kept:
+//!
+//! - `prefix ++ "v" ++ stream -> version`, the number of events of the stream;
+//! - `prefix ++ "e" ++ stream ++ version -> event`;
+//! - `prefix ++ "s" ++ stream -> (version, state)`, the latest snapshot of the stream.
+//!
+//! With [`EventStore::with_snapshots`], the state is saved every `n` events when appending, so
+//! [`EventStore::state`] only replays the events after the latest snapshot.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::EventStore;
+//! use vrs_core_sdk::{get, post, CallResult};
+//!
+//! #[derive(Debug, Decode, Encode)]
+//! #[codec(crate = vrs_core_sdk::codec)]
+//! pub enum AccountEvent {
+//!     Deposited(u128),
+//!     Withdrawn(u128),
+//! }
+//!
+//! fn apply(balance: u128, event: &AccountEvent) -> u128 {
+//!     match event {
+//!         AccountEvent::Deposited(amount) => balance + amount,
+//!         AccountEvent::Withdrawn(amount) => balance - amount,
+//!     }
+//! }
+//!
+//! const ACCOUNTS: EventStore<String, AccountEvent, u128> =
+//!     EventStore::new(b"account:", apply).with_snapshots(100);
+//!
+//! #[post]
+//! pub fn deposit(account: String, amount: u128) -> CallResult<u64> {
+//!     ACCOUNTS.append(&account, &AccountEvent::Deposited(amount))
+//! }
+//!
+//! #[post]
+//! pub fn withdraw(account: String, amount: u128) -> Result<u64, String> {
+//!     let (_, balance) = ACCOUNTS.state(&account).map_err(|e| e.to_string())?;
+//!     if balance < amount {
+//!         return Err("insufficient balance".to_string());
+//!     }
+//!     ACCOUNTS
+//!         .append(&account, &AccountEvent::Withdrawn(amount))
+//!         .map_err(|e| e.to_string())
+//! }
+//!
+//! #[get]
+//! pub fn history(account: String, from: u64) -> CallResult<Vec<(u64, AccountEvent)>> {
+//!     ACCOUNTS.events(&account, from, 100)
+//! }
+//! ```
+//!
+//! With the `mock` feature, the state is replayed from the latest snapshot:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::{mock, storage::EventStore};
+//!
+//! const COUNTERS: EventStore<String, i64, i64> =
+//!     EventStore::new(b"counter:", |n, delta| n + delta).with_snapshots(2);
+//!
+//! mock::reset();
+//! let stream = "a".to_string();
+//! for delta in [1, 2, 3] {
+//!     COUNTERS.append(&stream, &delta).unwrap();
+//! }
+//! assert_eq!(COUNTERS.snapshot(&stream).unwrap(), Some((2, 3)));
+//! assert_eq!(COUNTERS.state(&stream).unwrap(), (3, 6));
+//! assert_eq!(COUNTERS.events(&stream, 1, 10).unwrap(), vec![(2, 2), (3, 3)]);
+//! let replayed = COUNTERS.replay(&stream, 0, vec![], |mut v, version, _| {
+//!     v.push(version);
+//!     v
+//! });
+//! assert_eq!(replayed.unwrap(), (3, vec![1, 2, 3]));
+//!
+//! // a rejected write keeps the version unchanged
+//! mock::set_read_only(true);
+//! assert!(COUNTERS.append(&stream, &4).is_err());
+//! mock::set_read_only(false);
+//! assert_eq!(COUNTERS.version(&stream).unwrap(), 3);
+//! # }
+//! ```
+
+use super::{
+    decode_value, get, increment, key::KeyEncode, put, scan_prefix, transaction, Direction,
+    MAX_RANGE_LIMIT,
+};
+use crate::{
+    abi::{ApiRegistry, StorageKind, StorageLayout},
+    error::RuntimeError,
+    CallResult,
+};
+use codec::{Decode, Encode};
+use scale_info::{meta_type, TypeInfo};
+use std::marker::PhantomData;
+
+/// Streams of events folded into a state, see the [module](self) documentation.
+pub struct EventStore<K, E, S> {
+    prefix: &'static [u8],
+    apply: fn(S, &E) -> S,
+    snapshot_every: u64,
+    _marker: PhantomData<fn() -> K>,
+}
+
+impl<K, E, S> EventStore<K, E, S> {
+    /// Create a store whose state is folded from the events by `apply`.
+    pub const fn new(prefix: &'static [u8], apply: fn(S, &E) -> S) -> Self {
+        Self {
+            prefix,
+            apply,
+            snapshot_every: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Save the state of a stream every `n` events, `0` disables the snapshots.
+    pub const fn with_snapshots(self, n: u64) -> Self {
+        Self {
+            snapshot_every: n,
+            ..self
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+}
+
+impl<K: TypeInfo + 'static, E: TypeInfo + 'static, S> StorageLayout for EventStore<K, E, S> {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry) {
+        registry.register_storage(
+            name.to_string(),
+            StorageKind::EventStore,
+            self.prefix,
+            meta_type::<K>(),
+            meta_type::<E>(),
+        );
+    }
+}
+
+impl<K, E, S> EventStore<K, E, S>
+where
+    K: KeyEncode,
+    E: Encode + Decode,
+    S: Encode + Decode + Default,
+{
+    fn stream_key(&self, tag: &[u8], stream: &K) -> Vec<u8> {
+        let mut key = [self.prefix, tag].concat();
+        stream.encode_key_to(&mut key);
+        key
+    }
+
+    fn event_key(&self, stream: &K, version: u64) -> Vec<u8> {
+        let mut key = self.stream_key(b"e", stream);
+        version.encode_key_to(&mut key);
+        key
+    }
+
+    /// The number of events of `stream`, i.e. the version of its last event.
+    pub fn version(&self, stream: &K) -> CallResult<u64> {
+        let key = self.stream_key(b"v", stream);
+        get(&key)?
+            .map(|v| decode_value::<i64>(&key, &v).map(|version| version as u64))
+            .unwrap_or(Ok(0))
+    }
+
+    /// Append `event` to `stream` and return its version, saving a snapshot if it is due.
+    ///
+    /// The version, the event and the snapshot are written in a [`transaction`], so a failed
+    /// write leaves no gap in the versions.
+    pub fn append(&self, stream: &K, event: &E) -> CallResult<u64> {
+        transaction(|| {
+            let version = increment(self.stream_key(b"v", stream), 1)? as u64;
+            put(self.event_key(stream, version), event.encode())?;
+            if self.snapshot_every > 0 && version % self.snapshot_every == 0 {
+                let (at, state) = self.state(stream)?;
+                put(self.stream_key(b"s", stream), (at, state).encode())?;
+            }
+            Ok(version)
+        })?
+    }
+
+    /// Get at most `limit` events of `stream` after version `from`, the limit maximum is
+    /// `MAX_RANGE_LIMIT`.
+    pub fn events(&self, stream: &K, from: u64, limit: usize) -> CallResult<Vec<(u64, E)>> {
+        let prefix = self.stream_key(b"e", stream);
+        let start = self.event_key(stream, from);
+        scan_prefix(&prefix, &start, true, Direction::Forward, limit)?
+            .into_iter()
+            .map(|(key, value)| {
+                let version = u64::from_be_bytes(
+                    key[prefix.len()..]
+                        .try_into()
+                        .map_err(|_| malformed_event(&key))?,
+                );
+                Ok((version, decode_value(&key, &value)?))
+            })
+            .collect()
+    }
+
+    /// Fold the events of `stream` after version `from` into `init`, returning the version of
+    /// the last folded event along with the result.
+    pub fn replay<A>(
+        &self,
+        stream: &K,
+        from: u64,
+        init: A,
+        mut fold: impl FnMut(A, u64, E) -> A,
+    ) -> CallResult<(u64, A)> {
+        let (mut version, mut acc) = (from, init);
+        loop {
+            let batch = self.events(stream, version, MAX_RANGE_LIMIT - 1)?;
+            if batch.is_empty() {
+                return Ok((version, acc));
+            }
+            for (v, event) in batch {
+                acc = fold(acc, v, event);
+                version = v;
+            }
+        }
+    }
+
+    /// The latest snapshot of `stream`.
+    pub fn snapshot(&self, stream: &K) -> CallResult<Option<(u64, S)>> {
+        let key = self.stream_key(b"s", stream);
+        get(&key)?.map(|v| decode_value(&key, &v)).transpose()
+    }
+
+    /// The current version and state of `stream`, replayed from its latest snapshot.
+    pub fn state(&self, stream: &K) -> CallResult<(u64, S)> {
+        let (from, state) = self.snapshot(stream)?.unwrap_or_default();
+        self.replay(stream, from, state, |state, _, event| {
+            (self.apply)(state, &event)
+        })
+    }
+}
+
+fn malformed_event(key: &[u8]) -> RuntimeError {
+    RuntimeError::KvStorageError(format!(
+        "malformed event key 0x{}",
+        sp_core::hexdisplay::HexDisplay::from(&key)
+    ))
+}
@@ -0,0 +1,120 @@
+//! Results of the idempotent post functions.
+//!
+//! The first parameter of a `#[post(idempotent)]` function is its idempotency key, and the
+//! function returns a `Result` whose error implements `From<RuntimeError>`, e.g. `CallResult`.
+//! The generated entry records the SCALE encoded `Ok` result of the function under the function
+//! name and the encoded key, and returns the recorded result instead of running the function
+//! again when called with the same key, so a client may retry a call safely. An `Err` isn't
+//! recorded, so a failed call may be retried with the same key.
+//!
+//! The function runs in a [`transaction`](super::transaction) along with writing its record. If
+//! the record can't be read or written, the writes of the function are rolled back and the entry
+//! returns the `RuntimeError` converted into the error of the function instead of the result.
+//! The transaction needs the length-aware host, so the idempotent functions always fail with the
+//! `legacy-host` feature.
+//!
+//! The records expire after [`DEFAULT_TTL`] or after the `ttl` in seconds given to the
+//! attribute. They are written with [`put_with_ttl`](super::ttl::put_with_ttl), so the
+//! expired records are removed along with the other keys by
+//! [`purge_expired`](super::ttl::purge_expired).
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::nucleus;
+//!
+//! #[nucleus]
+//! pub mod nucleus {
+//!     use vrs_core_sdk::{post, CallResult};
+//!
+//!     #[post(idempotent, ttl = 3600)]
+//!     pub fn transfer(request_id: [u8; 16], to: String, amount: u128) -> CallResult<u64> {
+//!         // runs once for each `request_id` within an hour
+//!         Ok(0)
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! With the `mock` feature:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::error::RuntimeError;
+//! use vrs_core_sdk::{mock, post, storage, timer, CallResult};
+//!
+//! #[post(idempotent, ttl = 60)]
+//! fn charge(request_id: u32, amount: u64) -> CallResult<u64> {
+//!     if amount > 100 {
+//!         return Err(RuntimeError::LimitExceeded);
+//!     }
+//!     if amount == 0 {
+//!         // nothing to charge, only the record is written
+//!         return Ok(0);
+//!     }
+//!     Ok(storage::increment(b"charged", amount as i64)? as u64)
+//! }
+//!
+//! // decode the output of the generated entry
+//! fn call(args: impl Encode) -> CallResult<u64> {
+//!     let args = args.encode();
+//!     let ptr = __nucleus_post_charge(args.as_ptr(), args.len());
+//!     let len = u32::from_ne_bytes(unsafe { *(ptr as *const [u8; 4]) }) as usize;
+//!     let output = unsafe { std::slice::from_raw_parts(ptr.add(4), len) };
+//!     let ret = Option::<Vec<u8>>::decode(&mut &output[..]).unwrap().unwrap();
+//!     CallResult::<u64>::decode(&mut &ret[..]).unwrap()
+//! }
+//!
+//! mock::reset();
+//! assert_eq!(call((7u32, 5u64)).unwrap(), 5);
+//! assert_eq!(call((7u32, 5u64)).unwrap(), 5);
+//! assert_eq!(storage::increment(b"charged", 0).unwrap(), 5);
+//!
+//! // a failure isn't recorded
+//! assert!(call((8u32, 500u64)).is_err());
+//! assert_eq!(storage::idempotency::recorded("charge", &8u32.encode()).unwrap(), None);
+//!
+//! // the error of writing the record is returned instead of the result
+//! mock::set_read_only(true);
+//! assert!(matches!(call((9u32, 0u64)), Err(RuntimeError::ReadOnly)));
+//! mock::set_read_only(false);
+//!
+//! // and the writes of the function are rolled back, here the record fails on a malformed expiry
+//! let record_key = storage::idempotency::record_key("charge", &9u32.encode());
+//! storage::put([b"__vrs:ttl:k:".as_ref(), &record_key].concat(), b"malformed").unwrap();
+//! assert!(matches!(call((9u32, 5u64)), Err(RuntimeError::KvStorageError(_))));
+//! assert_eq!(storage::increment(b"charged", 0).unwrap(), 5);
+//!
+//! mock::set_now(timer::now() + 60_000);
+//! assert_eq!(call((7u32, 5u64)).unwrap(), 10);
+//! # }
+//! ```
+
+use super::{key::KeyEncode, ttl};
+use crate::CallResult;
+use std::time::Duration;
+
+const RECORD_PREFIX: &[u8] = b"__vrs:idem:";
+
+/// how long a result is recorded unless the function specifies a `ttl`
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The key of the result of `func` called with the encoded idempotency `key`.
+pub fn record_key(func: &str, key: &[u8]) -> Vec<u8> {
+    let mut raw = RECORD_PREFIX.to_vec();
+    func.encode_key_to(&mut raw);
+    raw.extend_from_slice(key);
+    raw
+}
+
+/// Get the recorded result of `func` for `key`, `None` if absent or expired.
+pub fn recorded(func: &str, key: &[u8]) -> CallResult<Option<Vec<u8>>> {
+    ttl::get(record_key(func, key))
+}
+
+/// Record the encoded result of `func` for `key` during `ttl`.
+pub fn record(func: &str, key: &[u8], result: &[u8], ttl: Duration) -> CallResult<()> {
+    ttl::put_with_ttl(record_key(func, key), result, ttl)
+}
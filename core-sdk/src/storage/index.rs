@@ -0,0 +1,261 @@
+//! Secondary indexes maintained by [`StorageMap`](super::StorageMap).
+//!
+//! An index maps a value extracted from the stored value to the primary key of the entry.
+//! The indexes attached to a map with `StorageMap::with_indexes` are updated on every
+//! `insert` and `remove` of the map. The quota and all the indexes are checked before anything
+//! is written, so a violated [`UniqueIndex`] leaves the storage untouched, and the value and its
+//! index entries are written in one [`transaction`](super::transaction), so a failed write
+//! leaves none of them.
+//!
+//! - [`UniqueIndex`] stores `prefix ++ index_key -> primary_key`;
+//! - [`Index`] stores `prefix ++ index_key ++ primary_key -> ()`.
+//!
+//! Both layouts use [`KeyEncode`](super::key::KeyEncode) so the index entries are ordered by
+//! the index key and can be scanned with `range`.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::{Index, StorageMap, UniqueIndex};
+//! use vrs_core_sdk::{get, post};
+//!
+//! #[derive(Debug, Decode, Encode)]
+//! #[codec(crate = vrs_core_sdk::codec)]
+//! pub struct User {
+//!     pub name: String,
+//!     pub age: u32,
+//! }
+//!
+//! const USERS_BY_NAME: UniqueIndex<u64, User, String> =
+//!     UniqueIndex::new(b"user_by_name:", |u| u.name.clone());
+//! const USERS_BY_AGE: Index<u64, User, u32> = Index::new(b"user_by_age:", |u| u.age);
+//! const USERS: StorageMap<u64, User> =
+//!     StorageMap::with_indexes(b"user:", &[&USERS_BY_NAME, &USERS_BY_AGE]);
+//!
+//! #[post]
+//! pub fn add_user(user: User) -> Result<u64, String> {
+//!     USERS.insert_auto(&user).map_err(|e| e.to_string())
+//! }
+//!
+//! #[get]
+//! pub fn find_user(name: String) -> Result<Option<u64>, String> {
+//!     USERS_BY_NAME.lookup(&name).map_err(|e| e.to_string())
+//! }
+//!
+//! #[get]
+//! pub fn users_of_age(age: u32) -> Result<Vec<u64>, String> {
+//!     USERS_BY_AGE.lookup(&age, 100).map_err(|e| e.to_string())
+//! }
+//! ```
+//!
+//! With the `mock` feature, an index failing to update rolls back the writes of the others:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::storage::{Indexer, StorageMap, UniqueIndex};
+//! use vrs_core_sdk::{error::RuntimeError, mock, CallResult};
+//!
+//! struct Broken;
+//!
+//! impl Indexer<u64, String> for Broken {
+//!     fn check(&self, _: &u64, _: &String) -> CallResult<()> {
+//!         Ok(())
+//!     }
+//!
+//!     fn update(&self, _: &u64, _: Option<&String>, new: Option<&String>) -> CallResult<()> {
+//!         match new {
+//!             Some(name) if name == "mallory" => Err(RuntimeError::ValueTooLarge),
+//!             _ => Ok(()),
+//!         }
+//!     }
+//! }
+//!
+//! const BY_NAME: UniqueIndex<u64, String, String> = UniqueIndex::new(b"by_name:", |n| n.clone());
+//! const NAMES: StorageMap<u64, String> = StorageMap::with_indexes(b"name:", &[&BY_NAME, &Broken]);
+//!
+//! mock::reset();
+//! NAMES.insert(&1, &"alice".to_string()).unwrap();
+//! assert!(matches!(NAMES.insert(&2, &"alice".to_string()), Err(RuntimeError::UniqueViolation)));
+//! assert!(NAMES.insert(&1, &"mallory".to_string()).is_err());
+//! assert_eq!(NAMES.get(&1).unwrap(), Some("alice".to_string()));
+//! assert_eq!(BY_NAME.lookup(&"alice".to_string()).unwrap(), Some(1));
+//! assert_eq!(BY_NAME.lookup(&"mallory".to_string()).unwrap(), None);
+//! NAMES.remove(&1).unwrap();
+//! assert_eq!(BY_NAME.lookup(&"alice".to_string()).unwrap(), None);
+//! # }
+//! ```
+
+use super::{
+    del, get,
+    key::{KeyDecode, KeyEncode},
+    put, scan_prefix, Direction,
+};
+use crate::{error::RuntimeError, CallResult};
+use std::marker::PhantomData;
+
+/// An index which is kept in sync with the entries of a map.
+pub trait Indexer<K, V> {
+    /// Check whether `value` can be stored under `key` without violating the index.
+    fn check(&self, key: &K, value: &V) -> CallResult<()>;
+
+    /// Update the index entries of `key` from `old` to `new`.
+    fn update(&self, key: &K, old: Option<&V>, new: Option<&V>) -> CallResult<()>;
+}
+
+fn index_key<I: KeyEncode>(prefix: &[u8], index: &I) -> Vec<u8> {
+    let mut raw = prefix.to_vec();
+    index.encode_key_to(&mut raw);
+    raw
+}
+
+fn malformed_index(key: &[u8]) -> RuntimeError {
+    RuntimeError::KvStorageError(format!(
+        "malformed index entry 0x{}",
+        sp_core::hexdisplay::HexDisplay::from(&key)
+    ))
+}
+
+/// An index whose keys identify at most one entry of the map.
+pub struct UniqueIndex<K, V, I> {
+    prefix: &'static [u8],
+    extract: fn(&V) -> I,
+    _marker: PhantomData<fn() -> K>,
+}
+
+impl<K, V, I> UniqueIndex<K, V, I> {
+    pub const fn new(prefix: &'static [u8], extract: fn(&V) -> I) -> Self {
+        Self {
+            prefix,
+            extract,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+}
+
+impl<K: KeyDecode, V, I: KeyEncode + KeyDecode> UniqueIndex<K, V, I> {
+    /// Get the primary key of the entry with the index key.
+    pub fn lookup(&self, index: &I) -> CallResult<Option<K>> {
+        let key = index_key(self.prefix, index);
+        get(&key)?
+            .map(|v| K::decode_key(&v).map_err(|_| malformed_index(&key)))
+            .transpose()
+    }
+
+    /// Get at most `limit` `(index_key, primary_key)` pairs starting from `start`.
+    pub fn range(&self, start: &I, direction: Direction, limit: usize) -> CallResult<Vec<(I, K)>> {
+        let start = index_key(self.prefix, start);
+        scan_prefix(self.prefix, &start, false, direction, limit)?
+            .into_iter()
+            .map(|(key, value)| {
+                let index = I::decode_key(&key[self.prefix.len()..]);
+                let primary = K::decode_key(&value);
+                index
+                    .and_then(|i| primary.map(|k| (i, k)))
+                    .map_err(|_| malformed_index(&key))
+            })
+            .collect()
+    }
+}
+
+impl<K: KeyEncode, V, I: KeyEncode + PartialEq> Indexer<K, V> for UniqueIndex<K, V, I> {
+    fn check(&self, key: &K, value: &V) -> CallResult<()> {
+        match get(index_key(self.prefix, &(self.extract)(value)))? {
+            Some(owner) if owner != key.encode_key() => Err(RuntimeError::UniqueViolation),
+            _ => Ok(()),
+        }
+    }
+
+    fn update(&self, key: &K, old: Option<&V>, new: Option<&V>) -> CallResult<()> {
+        let old = old.map(self.extract);
+        let new = new.map(self.extract);
+        if old == new {
+            return Ok(());
+        }
+        if let Some(old) = old {
+            del(index_key(self.prefix, &old))?;
+        }
+        if let Some(new) = new {
+            put(index_key(self.prefix, &new), key.encode_key())?;
+        }
+        Ok(())
+    }
+}
+
+/// An index whose keys may be shared by many entries of the map.
+pub struct Index<K, V, I> {
+    prefix: &'static [u8],
+    extract: fn(&V) -> I,
+    _marker: PhantomData<fn() -> K>,
+}
+
+impl<K, V, I> Index<K, V, I> {
+    pub const fn new(prefix: &'static [u8], extract: fn(&V) -> I) -> Self {
+        Self {
+            prefix,
+            extract,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+}
+
+impl<K: KeyEncode + KeyDecode, V, I: KeyEncode + KeyDecode> Index<K, V, I> {
+    /// Get at most `limit` primary keys of the entries with the index key.
+    pub fn lookup(&self, index: &I, limit: usize) -> CallResult<Vec<K>> {
+        let prefix = index_key(self.prefix, index);
+        scan_prefix(&prefix, &prefix, false, Direction::Forward, limit)?
+            .into_iter()
+            .map(|(key, _)| K::decode_key(&key[prefix.len()..]).map_err(|_| malformed_index(&key)))
+            .collect()
+    }
+
+    /// Get at most `limit` `(index_key, primary_key)` pairs starting from `start`.
+    pub fn range(&self, start: &I, direction: Direction, limit: usize) -> CallResult<Vec<(I, K)>> {
+        let start = index_key(self.prefix, start);
+        scan_prefix(self.prefix, &start, false, direction, limit)?
+            .into_iter()
+            .map(|(key, _)| {
+                let mut input = &key[self.prefix.len()..];
+                let index = I::decode_key_from(&mut input);
+                let primary = K::decode_key(input);
+                index
+                    .and_then(|i| primary.map(|k| (i, k)))
+                    .map_err(|_| malformed_index(&key))
+            })
+            .collect()
+    }
+}
+
+impl<K: KeyEncode, V, I: KeyEncode + PartialEq> Indexer<K, V> for Index<K, V, I> {
+    fn check(&self, _key: &K, _value: &V) -> CallResult<()> {
+        Ok(())
+    }
+
+    fn update(&self, key: &K, old: Option<&V>, new: Option<&V>) -> CallResult<()> {
+        let old = old.map(self.extract);
+        let new = new.map(self.extract);
+        if old == new {
+            return Ok(());
+        }
+        if let Some(old) = old {
+            let mut entry = index_key(self.prefix, &old);
+            key.encode_key_to(&mut entry);
+            del(entry)?;
+        }
+        if let Some(new) = new {
+            let mut entry = index_key(self.prefix, &new);
+            key.encode_key_to(&mut entry);
+            put(entry, [])?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+//! Order-preserving encoding of storage keys.
+//!
+//! Unlike SCALE, the bytes produced by [`KeyEncode`] sort in the same order as the logical
+//! values, so composite keys like `(user_id, timestamp)` can be scanned with `get_range` and
+//! `search`.
+//!
+//! - unsigned integers are written in big-endian;
+//! - signed integers are written in big-endian with the sign bit flipped;
+//! - strings and byte vectors escape `0x00` as `0x00 0xff` and end with `0x00 0x01`, so a
+//!   value always sorts before any longer value it is a prefix of;
+//! - tuples, structs and fixed size arrays are the concatenation of their components;
+//! - enums and `Option` start with the index of the variant.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::storage::key::{KeyDecode, KeyEncode};
+//!
+//! #[derive(Debug, PartialEq, KeyEncode, KeyDecode)]
+//! pub struct Reading {
+//!     pub sensor: String,
+//!     pub timestamp: i64,
+//! }
+//!
+//! let a = Reading { sensor: "a".to_string(), timestamp: -1 }.encode_key();
+//! let b = Reading { sensor: "a".to_string(), timestamp: 1 }.encode_key();
+//! let c = Reading { sensor: "ab".to_string(), timestamp: -1 }.encode_key();
+//! assert!(a < b && b < c);
+//! assert_eq!(
+//!     Reading::decode_key(&b).unwrap(),
+//!     Reading { sensor: "a".to_string(), timestamp: 1 }
+//! );
+//! ```
+
+use crate::AccountId;
+pub use codec::Error;
+pub use vrs_core_macros::{KeyDecode, KeyEncode};
+
+const ESCAPE: u8 = 0x00;
+const ESCAPED_ESCAPE: u8 = 0xff;
+const TERMINATOR: u8 = 0x01;
+
+/// Encode a value into bytes which sort in the same order as the value.
+pub trait KeyEncode {
+    fn encode_key_to(&self, out: &mut Vec<u8>);
+
+    fn encode_key(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_key_to(&mut out);
+        out
+    }
+}
+
+/// Decode a value encoded by [`KeyEncode`].
+pub trait KeyDecode: Sized {
+    /// Decode a value from the beginning of `input` and advance it.
+    fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error>;
+
+    /// Decode a value which spans the whole `input`.
+    fn decode_key(mut input: &[u8]) -> Result<Self, Error> {
+        let value = Self::decode_key_from(&mut input)?;
+        if !input.is_empty() {
+            return Err("trailing bytes after the key".into());
+        }
+        Ok(value)
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if input.len() < len {
+        return Err("not enough bytes to decode the key".into());
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+/// Write a variable length component, escaping `0x00` and appending the terminator.
+pub fn encode_bytes_to(bytes: &[u8], out: &mut Vec<u8>) {
+    for b in bytes {
+        out.push(*b);
+        if *b == ESCAPE {
+            out.push(ESCAPED_ESCAPE);
+        }
+    }
+    out.push(ESCAPE);
+    out.push(TERMINATOR);
+}
+
+/// Read a variable length component written by [`encode_bytes_to`].
+pub fn decode_bytes_from(input: &mut &[u8]) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    loop {
+        let b = take(input, 1)?[0];
+        if b != ESCAPE {
+            bytes.push(b);
+            continue;
+        }
+        match take(input, 1)?[0] {
+            ESCAPED_ESCAPE => bytes.push(ESCAPE),
+            TERMINATOR => return Ok(bytes),
+            _ => return Err("invalid escape sequence in the key".into()),
+        }
+    }
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {$(
+        impl KeyEncode for $t {
+            fn encode_key_to(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+        }
+
+        impl KeyDecode for $t {
+            fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+                let bytes = take(input, core::mem::size_of::<$t>())?;
+                Ok(<$t>::from_be_bytes(bytes.try_into().expect("length checked; qed")))
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed {
+    ($($t:ty => $u:ty),*) => {$(
+        impl KeyEncode for $t {
+            fn encode_key_to(&self, out: &mut Vec<u8>) {
+                ((*self as $u) ^ (1 << (<$u>::BITS - 1))).encode_key_to(out);
+            }
+        }
+
+        impl KeyDecode for $t {
+            fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+                let flipped = <$u>::decode_key_from(input)?;
+                Ok((flipped ^ (1 << (<$u>::BITS - 1))) as $t)
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128);
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+impl KeyEncode for bool {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl KeyDecode for bool {
+    fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+        match take(input, 1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err("invalid bool in the key".into()),
+        }
+    }
+}
+
+impl KeyEncode for () {
+    fn encode_key_to(&self, _out: &mut Vec<u8>) {}
+}
+
+impl KeyDecode for () {
+    fn decode_key_from(_input: &mut &[u8]) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl KeyEncode for str {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        encode_bytes_to(self.as_bytes(), out);
+    }
+}
+
+impl KeyEncode for String {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        self.as_str().encode_key_to(out);
+    }
+}
+
+impl KeyDecode for String {
+    fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+        String::from_utf8(decode_bytes_from(input)?).map_err(|_| "invalid utf8 in the key".into())
+    }
+}
+
+impl KeyEncode for [u8] {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        encode_bytes_to(self, out);
+    }
+}
+
+impl KeyEncode for Vec<u8> {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        encode_bytes_to(self, out);
+    }
+}
+
+impl KeyDecode for Vec<u8> {
+    fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+        decode_bytes_from(input)
+    }
+}
+
+impl<const N: usize> KeyEncode for [u8; N] {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl<const N: usize> KeyDecode for [u8; N] {
+    fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+        Ok(take(input, N)?.try_into().expect("length checked; qed"))
+    }
+}
+
+impl KeyEncode for AccountId {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_ref());
+    }
+}
+
+impl KeyDecode for AccountId {
+    fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+        <[u8; 32]>::decode_key_from(input).map(AccountId::new)
+    }
+}
+
+impl<T: KeyEncode> KeyEncode for Option<T> {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(v) => {
+                out.push(1);
+                v.encode_key_to(out);
+            }
+        }
+    }
+}
+
+impl<T: KeyDecode> KeyDecode for Option<T> {
+    fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+        match take(input, 1)?[0] {
+            0 => Ok(None),
+            1 => T::decode_key_from(input).map(Some),
+            _ => Err("invalid option in the key".into()),
+        }
+    }
+}
+
+impl<T: KeyEncode + ?Sized> KeyEncode for &T {
+    fn encode_key_to(&self, out: &mut Vec<u8>) {
+        (**self).encode_key_to(out);
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: KeyEncode),+> KeyEncode for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode_key_to(&self, out: &mut Vec<u8>) {
+                let ($($name,)+) = self;
+                $($name.encode_key_to(out);)+
+            }
+        }
+
+        impl<$($name: KeyDecode),+> KeyDecode for ($($name,)+) {
+            fn decode_key_from(input: &mut &[u8]) -> Result<Self, Error> {
+                Ok(($($name::decode_key_from(input)?,)+))
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
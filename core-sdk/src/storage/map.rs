@@ -0,0 +1,240 @@
+use super::{
+    compress::{decode_stored, encode_stored, Compression},
+    del, exists, get,
+    index::Indexer,
+    key::{KeyDecode, KeyEncode},
+    list_page, prefix_start, prefix_usage, put, scan_prefix, transaction, value_len, Cursor,
+    Direction, Page, Quota, Sequence, Usage,
+};
+use crate::abi::{ApiRegistry, StorageKind, StorageLayout};
+use crate::error::RuntimeError;
+use crate::CallResult;
+use codec::{Decode, Encode};
+use scale_info::{meta_type, TypeInfo};
+use std::marker::PhantomData;
+
+/// A typed map storing SCALE encoded values under `prefix ++ key`, the keys are encoded with
+/// [`KeyEncode`] so the entries are ordered by key.
+///
+/// # Examples
+///
+/// ```
+/// use vrs_core_sdk::codec::{Decode, Encode};
+/// use vrs_core_sdk::{get, post, storage::StorageMap};
+///
+/// #[derive(Debug, Decode, Encode)]
+/// #[codec(crate = vrs_core_sdk::codec)]
+/// pub struct User {
+///     pub name: String,
+/// }
+///
+/// const USERS: StorageMap<u64, User> = StorageMap::new(b"user:");
+///
+/// #[post]
+/// pub fn add_user(user: User) -> Result<u64, String> {
+///     USERS.insert_auto(&user).map_err(|e| e.to_string())
+/// }
+///
+/// #[get]
+/// pub fn get_user(id: u64) -> Result<Option<User>, String> {
+///     USERS.get(&id).map_err(|e| e.to_string())
+/// }
+/// ```
+pub struct StorageMap<K: 'static, V: 'static> {
+    prefix: &'static [u8],
+    indexes: &'static [&'static dyn Indexer<K, V>],
+    quota: Option<Quota>,
+    compression: Option<Compression>,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> StorageMap<K, V> {
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self::with_indexes(prefix, &[])
+    }
+
+    /// Create a map whose secondary indexes are updated atomically with the value on every
+    /// `insert` and `remove`.
+    pub const fn with_indexes(
+        prefix: &'static [u8],
+        indexes: &'static [&'static dyn Indexer<K, V>],
+    ) -> Self {
+        Self {
+            prefix,
+            indexes,
+            quota: None,
+            compression: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Limit the entries of the map, `insert` fails with `RuntimeError::QuotaExceeded` if it
+    /// would exceed the quota.
+    pub const fn with_quota(self, quota: Quota) -> Self {
+        Self {
+            quota: Some(quota),
+            ..self
+        }
+    }
+
+    /// Compress the values of the map, see [`compress`](super::compress).
+    pub const fn with_compression(self, compression: Compression) -> Self {
+        Self {
+            compression: Some(compression),
+            ..self
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    /// The number of entries and the total bytes of the map, not including its indexes.
+    pub fn usage(&self) -> CallResult<Usage> {
+        prefix_usage(self.prefix)
+    }
+}
+
+impl<K: TypeInfo + 'static, V: TypeInfo + 'static> StorageLayout for StorageMap<K, V> {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry) {
+        registry.register_storage(
+            name.to_string(),
+            StorageKind::Map {
+                compressed: self.compression.is_some(),
+            },
+            self.prefix,
+            meta_type::<K>(),
+            meta_type::<V>(),
+        );
+    }
+}
+
+impl<K: KeyEncode, V: Encode + Decode> StorageMap<K, V> {
+    /// The raw storage key of `key`.
+    pub fn storage_key(&self, key: &K) -> Vec<u8> {
+        let mut raw = self.prefix.to_vec();
+        key.encode_key_to(&mut raw);
+        raw
+    }
+
+    pub fn get(&self, key: &K) -> CallResult<Option<V>> {
+        let key = self.storage_key(key);
+        get(&key)?
+            .map(|v| decode_stored(self.compression.as_ref(), &key, &v))
+            .transpose()
+    }
+
+    pub fn contains_key(&self, key: &K) -> CallResult<bool> {
+        exists(self.storage_key(key))
+    }
+
+    /// Insert `value` under `key` and update the indexes, failing without writing anything if
+    /// the quota or a unique index would be violated. The value and the index entries are
+    /// written in a [`transaction`](super::transaction).
+    pub fn insert(&self, key: &K, value: &V) -> CallResult<()> {
+        let raw_key = self.storage_key(key);
+        let encoded = encode_stored(self.compression.as_ref(), value);
+        if let Some(quota) = &self.quota {
+            let (keys, bytes) = match value_len(&raw_key)? {
+                Some(old) => (0, encoded.len() as i64 - old as i64),
+                None => (1, (raw_key.len() + encoded.len()) as i64),
+            };
+            quota.check(self.prefix, keys, bytes)?;
+        }
+        if self.indexes.is_empty() {
+            return put(raw_key, encoded);
+        }
+        // check all indexes before writing anything so a violation leaves no partial update
+        for index in self.indexes {
+            index.check(key, value)?;
+        }
+        let old = self.get(key)?;
+        transaction(|| {
+            for index in self.indexes {
+                index.update(key, old.as_ref(), Some(value))?;
+            }
+            put(raw_key, encoded)
+        })?
+    }
+
+    /// Remove the entry of `key` and its index entries.
+    pub fn remove(&self, key: &K) -> CallResult<()> {
+        let raw_key = self.storage_key(key);
+        if self.indexes.is_empty() {
+            return del(raw_key);
+        }
+        let Some(old) = self.get(key)? else {
+            return del(raw_key);
+        };
+        transaction(|| {
+            for index in self.indexes {
+                index.update(key, Some(&old), None)?;
+            }
+            del(raw_key)
+        })?
+    }
+}
+
+impl<K: KeyEncode + KeyDecode, V: Encode + Decode> StorageMap<K, V> {
+    /// Get at most `limit` entries strictly after `after` in `direction`, starting from the
+    /// first (or the last in reverse) entry if `after` is `None`.
+    pub fn range(
+        &self,
+        after: Option<&K>,
+        direction: Direction,
+        limit: usize,
+    ) -> CallResult<Vec<(K, V)>> {
+        let start = match after {
+            Some(key) => self.storage_key(key),
+            None => prefix_start(self.prefix, direction)?,
+        };
+        scan_prefix(self.prefix, &start, after.is_some(), direction, limit)?
+            .into_iter()
+            .map(|(key, value)| self.decode_entry(key, value))
+            .collect()
+    }
+
+    /// Get a page of at most `limit` entries, continuing from `cursor` if provided.
+    pub fn page(
+        &self,
+        cursor: Option<&Cursor>,
+        limit: usize,
+        direction: Direction,
+    ) -> CallResult<Page<(K, V)>> {
+        let page = list_page(self.prefix, cursor, limit, direction)?;
+        let items = page
+            .items
+            .into_iter()
+            .map(|(key, value)| self.decode_entry(key, value))
+            .collect::<CallResult<_>>()?;
+        Ok(Page {
+            items,
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    fn decode_entry(&self, key: Vec<u8>, value: Vec<u8>) -> CallResult<(K, V)> {
+        let k = K::decode_key(&key[self.prefix.len()..]).map_err(|_| {
+            RuntimeError::KvStorageError(format!(
+                "failed to decode the key 0x{}",
+                sp_core::hexdisplay::HexDisplay::from(&key)
+            ))
+        })?;
+        Ok((k, decode_stored(self.compression.as_ref(), &key, &value)?))
+    }
+}
+
+impl<V: Encode + Decode> StorageMap<u64, V> {
+    /// The sequence allocating the ids of `insert_auto`, its counter is stored under
+    /// `__vrs:seq:` ++ prefix.
+    pub fn sequence(&self) -> Sequence {
+        Sequence::with_key([&b"__vrs:seq:"[..], self.prefix].concat())
+    }
+
+    /// Insert the value under the next id of the map's sequence and return the id.
+    pub fn insert_auto(&self, value: &V) -> CallResult<u64> {
+        let id = self.sequence().next()?;
+        self.insert(&id, value)?;
+        Ok(id)
+    }
+}
@@ -0,0 +1,226 @@
+//! An append-only log committed by a merkle tree.
+//!
+//! The log uses the same tree as the [`proof`] of the kv storage, so the leaves
+//! hash `0x00 ++ data` and a proof is checked with
+//! [`verify_inclusion`](super::proof::verify_inclusion) and
+//! [`verify_consistency`](super::proof::verify_consistency) by anyone holding the root, e.g.
+//! another chain the root is published to.
+//!
+//! Under the prefix of the log are stored the number of leaves, the data of each leaf and the
+//! root of every complete subtree. Appending a leaf computes the subtrees it completes, and any
+//! root or proof is derived from `O(log n)` of them.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::storage::proof::{ConsistencyProof, Hash, Proof};
+//! use vrs_core_sdk::storage::MerkleLog;
+//! use vrs_core_sdk::{get, post, CallResult};
+//!
+//! const READINGS: MerkleLog = MerkleLog::new(b"readings:");
+//!
+//! #[post]
+//! pub fn record(reading: Vec<u8>) -> CallResult<u64> {
+//!     READINGS.append(&reading)
+//! }
+//!
+//! #[get]
+//! pub fn checkpoint() -> CallResult<(u64, Hash)> {
+//!     Ok((READINGS.len()?, READINGS.root()?))
+//! }
+//!
+//! #[get]
+//! pub fn prove_reading(index: u64, size: u64) -> CallResult<Proof> {
+//!     READINGS.inclusion_proof(index, size)
+//! }
+//!
+//! #[get]
+//! pub fn prove_extension(old_size: u64, new_size: u64) -> CallResult<ConsistencyProof> {
+//!     READINGS.consistency_proof(old_size, new_size)
+//! }
+//! ```
+//!
+//! With the `mock` feature, the proofs verify against the roots of the log:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::storage::{proof, MerkleLog};
+//! use vrs_core_sdk::mock;
+//!
+//! const LOG: MerkleLog = MerkleLog::new(b"log:");
+//!
+//! mock::reset();
+//! for i in 0u8..5 {
+//!     LOG.append(&[i]).unwrap();
+//! }
+//! let old_root = LOG.root_at(3).unwrap();
+//! let root = LOG.root().unwrap();
+//! let inclusion = LOG.inclusion_proof(2, 5).unwrap();
+//! assert!(proof::verify_inclusion(&root, proof::leaf_hash(&[2]), 2, 5, &inclusion.path));
+//! assert!(!proof::verify_inclusion(&root, proof::leaf_hash(&[3]), 2, 5, &inclusion.path));
+//! let consistency = LOG.consistency_proof(3, 5).unwrap();
+//! assert!(proof::verify_consistency(&old_root, &root, &consistency));
+//! # }
+//! ```
+
+use super::{
+    decode_value, get, increment,
+    proof::{self, ConsistencyProof, Hash, Proof},
+    put,
+};
+use crate::{
+    abi::{ApiRegistry, StorageKind, StorageLayout},
+    error::RuntimeError,
+    CallResult,
+};
+use scale_info::meta_type;
+
+/// An append-only merkle log, see the [module](self) documentation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerkleLog {
+    prefix: &'static [u8],
+}
+
+impl MerkleLog {
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self { prefix }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    fn size_key(&self) -> Vec<u8> {
+        [self.prefix, b"n"].concat()
+    }
+
+    fn data_key(&self, index: u64) -> Vec<u8> {
+        [self.prefix, b"d", &index.to_be_bytes()[..]].concat()
+    }
+
+    /// The key of the root of the complete subtree of `2^height` leaves at `index` of its level.
+    fn node_key(&self, height: u8, index: u64) -> Vec<u8> {
+        [self.prefix, b"t", &[height][..], &index.to_be_bytes()[..]].concat()
+    }
+
+    fn node(&self, height: u8, index: u64) -> CallResult<Hash> {
+        let key = self.node_key(height, index);
+        get(&key)?.and_then(|v| v.try_into().ok()).ok_or_else(|| {
+            RuntimeError::KvStorageError(format!(
+                "missing node 0x{} of the merkle log",
+                sp_core::hexdisplay::HexDisplay::from(&key)
+            ))
+        })
+    }
+
+    /// The root of the `size` leaves from `start`, which are aligned as in the tree.
+    fn subtree(&self, start: u64, size: u64) -> CallResult<Hash> {
+        if size.is_power_of_two() {
+            return self.node(size.trailing_zeros() as u8, start / size);
+        }
+        let k = proof::split(size);
+        Ok(proof::node_hash(
+            &self.subtree(start, k)?,
+            &self.subtree(start + k, size - k)?,
+        ))
+    }
+
+    /// The number of leaves.
+    pub fn len(&self) -> CallResult<u64> {
+        let key = self.size_key();
+        get(&key)?
+            .map(|v| decode_value::<i64>(&key, &v).map(|size| size as u64))
+            .unwrap_or(Ok(0))
+    }
+
+    pub fn is_empty(&self) -> CallResult<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Append a leaf and return its index.
+    pub fn append(&self, data: &[u8]) -> CallResult<u64> {
+        let index = increment(self.size_key(), 1)? as u64 - 1;
+        put(self.data_key(index), data)?;
+        let mut hash = proof::leaf_hash(data);
+        put(self.node_key(0, index), hash)?;
+        // the leaf completes a subtree of each height where it is the last leaf
+        let mut node = index;
+        let mut height = 0;
+        while node & 1 == 1 {
+            let left = self.node(height, node - 1)?;
+            hash = proof::node_hash(&left, &hash);
+            node >>= 1;
+            height += 1;
+            put(self.node_key(height, node), hash)?;
+        }
+        Ok(index)
+    }
+
+    /// Get the data of the leaf at `index`.
+    pub fn get(&self, index: u64) -> CallResult<Option<Vec<u8>>> {
+        get(self.data_key(index))
+    }
+
+    /// The current root, the hash of nothing if the log is empty.
+    pub fn root(&self) -> CallResult<Hash> {
+        self.root_at(self.len()?)
+    }
+
+    /// The root when the log had `size` leaves.
+    pub fn root_at(&self, size: u64) -> CallResult<Hash> {
+        if size > self.len()? {
+            return Err(RuntimeError::NotFound);
+        }
+        if size == 0 {
+            return Ok(proof::root(&[]));
+        }
+        self.subtree(0, size)
+    }
+
+    /// The proof of the leaf at `index` in the log of `size` leaves.
+    pub fn inclusion_proof(&self, index: u64, size: u64) -> CallResult<Proof> {
+        if index >= size || size > self.len()? {
+            return Err(RuntimeError::NotFound);
+        }
+        let path = proof::inclusion_path_with(index, 0, size, &mut |start, size| {
+            self.subtree(start, size)
+        })?;
+        Ok(Proof {
+            leaf_index: index,
+            leaf_count: size,
+            path,
+        })
+    }
+
+    /// The proof that the log of `old_size` leaves is a prefix of the log of `new_size` leaves.
+    pub fn consistency_proof(&self, old_size: u64, new_size: u64) -> CallResult<ConsistencyProof> {
+        if old_size > new_size || new_size > self.len()? {
+            return Err(RuntimeError::NotFound);
+        }
+        let path = if old_size == 0 {
+            vec![]
+        } else {
+            proof::consistency_path_with(old_size, 0, new_size, true, &mut |start, size| {
+                self.subtree(start, size)
+            })?
+        };
+        Ok(ConsistencyProof {
+            old_size,
+            new_size,
+            path,
+        })
+    }
+}
+
+impl StorageLayout for MerkleLog {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry) {
+        registry.register_storage(
+            name.to_string(),
+            StorageKind::MerkleLog,
+            self.prefix,
+            meta_type::<u64>(),
+            meta_type::<Vec<u8>>(),
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Schema versioning for the data stored by a nucleus.
+//!
+//! The SDK records the version of the stored data under [`STORAGE_VERSION_KEY`]. A nucleus
+//! which never recorded a version is considered to be at [`DEFAULT_STORAGE_VERSION`].
+//!
+//! Functions annotated with `#[migration(from = 1, to = 2)]` inside a `#[nucleus]` module are
+//! collected by the macro. The pending migrations are executed in order at the beginning of the
+//! `#[init]` function and by the generated `__nucleus_upgrade` entry, which returns the
+//! `CallResult` of [`run`].
+//!
+//! If a migration fails, an `#[init]` function without return value logs the error and returns
+//! before its body runs, while an `#[init]` function returning a `Result` returns the error
+//! converted with `From<RuntimeError>`, e.g. `CallResult<()>`.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::{nucleus, CallResult};
+//!
+//! #[nucleus]
+//! pub mod nucleus {
+//!     use super::*;
+//!     use vrs_core_sdk::{init, migration, storage::migration::translate};
+//!
+//!     #[derive(Decode)]
+//!     #[codec(crate = vrs_core_sdk::codec)]
+//!     pub struct UserV1 {
+//!         pub name: String,
+//!     }
+//!
+//!     #[derive(Encode)]
+//!     #[codec(crate = vrs_core_sdk::codec)]
+//!     pub struct UserV2 {
+//!         pub name: String,
+//!         pub age: u32,
+//!     }
+//!
+//!     #[migration(from = 1, to = 2)]
+//!     pub fn add_age() -> CallResult<()> {
+//!         translate(b"user:", 100, |_, old: UserV1| {
+//!             Some(UserV2 { name: old.name, age: 0 })
+//!         })?;
+//!         Ok(())
+//!     }
+//!
+//!     // `add_age` runs here if the stored data is still at version 1
+//!     #[init]
+//!     pub fn init() {}
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! With the `mock` feature, a failed migration keeps the storage version of the last successful
+//! one and stops the `#[init]` function:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::storage::{self, migration::{self, Migration}};
+//! use vrs_core_sdk::{error::RuntimeError, mock, nucleus, CallResult};
+//!
+//! #[nucleus]
+//! mod nucleus {
+//!     use vrs_core_sdk::{error::RuntimeError, init, migration, storage, CallResult};
+//!
+//!     #[migration(from = 1, to = 2)]
+//!     fn broken() -> CallResult<()> {
+//!         Err(RuntimeError::NotFound)
+//!     }
+//!
+//!     #[init]
+//!     fn init() {
+//!         storage::put(b"initialized", []).unwrap();
+//!     }
+//! }
+//!
+//! mock::reset();
+//! nucleus::__nucleus_init([].as_ptr(), 0);
+//! assert!(!storage::exists(b"initialized").unwrap());
+//! assert_eq!(migration::storage_version().unwrap(), 1);
+//!
+//! fn ok() -> CallResult<()> {
+//!     Ok(())
+//! }
+//! fn fail() -> CallResult<()> {
+//!     Err(RuntimeError::NotFound)
+//! }
+//! let migrations = [
+//!     Migration { from: 1, to: 2, run: ok },
+//!     Migration { from: 2, to: 3, run: fail },
+//! ];
+//! assert!(migration::run(&migrations).is_err());
+//! assert_eq!(migration::storage_version().unwrap(), 2);
+//! # }
+//! ```
+
+use super::{decode_value, get, get_range, put, Direction, MAX_RANGE_LIMIT};
+use crate::{error::RuntimeError, CallResult};
+use codec::{Decode, Encode};
+
+/// the key holding the version of the stored data
+pub const STORAGE_VERSION_KEY: &[u8] = b"__vrs:storage_version";
+
+/// the version of a nucleus which never recorded its storage version
+pub const DEFAULT_STORAGE_VERSION: u32 = 1;
+
+/// A migration upgrading the stored data from one version to another.
+#[derive(Clone, Copy, Debug)]
+pub struct Migration {
+    pub from: u32,
+    pub to: u32,
+    pub run: fn() -> CallResult<()>,
+}
+
+/// Get the version of the stored data.
+pub fn storage_version() -> CallResult<u32> {
+    match get(STORAGE_VERSION_KEY)? {
+        Some(v) => decode_value(STORAGE_VERSION_KEY, &v),
+        None => Ok(DEFAULT_STORAGE_VERSION),
+    }
+}
+
+/// Set the version of the stored data.
+pub fn set_storage_version(version: u32) -> CallResult<()> {
+    put(STORAGE_VERSION_KEY, version.encode())
+}
+
+/// Run the migrations starting from the current storage version and return the version
+/// after all of them are applied.
+///
+/// Each migration is followed by an update of the storage version, so a chain like
+/// `1 -> 2 -> 3` is applied step by step. Migrations that don't start from the current
+/// version are skipped.
+pub fn run(migrations: &[Migration]) -> CallResult<u32> {
+    let mut version = storage_version()?;
+    while let Some(migration) = migrations.iter().find(|m| m.from == version) {
+        if migration.to <= migration.from {
+            return Err(RuntimeError::KvStorageError(format!(
+                "invalid migration from {} to {}",
+                migration.from, migration.to
+            )));
+        }
+        (migration.run)()?;
+        set_storage_version(migration.to)?;
+        version = migration.to;
+    }
+    Ok(version)
+}
+
+/// Translate all values under `prefix` from `Old` to `New`, returning the number of
+/// translated entries.
+///
+/// The entries are read with `get_range` in batches of at most `batch_size`. If `f` returns
+/// `None` the entry is removed.
+pub fn translate<Old: Decode, New: Encode>(
+    prefix: impl AsRef<[u8]>,
+    batch_size: usize,
+    mut f: impl FnMut(&[u8], Old) -> Option<New>,
+) -> CallResult<u64> {
+    let prefix = prefix.as_ref();
+    if batch_size == 0 || batch_size > MAX_RANGE_LIMIT {
+        return Err(RuntimeError::LimitExceeded);
+    }
+    let mut translated = 0u64;
+    let mut start = prefix.to_vec();
+    loop {
+        let batch = get_range(&start, Direction::Forward, batch_size)?;
+        let exhausted = batch.len() < batch_size;
+        for (key, value) in batch {
+            if !key.starts_with(prefix) {
+                return Ok(translated);
+            }
+            let old: Old = decode_value(&key, &value)?;
+            match f(&key, old) {
+                Some(new) => put(&key, new.encode())?,
+                None => super::del(&key)?,
+            }
+            translated += 1;
+            start = key;
+            start.push(0);
+        }
+        if exhausted {
+            return Ok(translated);
+        }
+    }
+}
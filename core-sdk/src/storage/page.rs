@@ -0,0 +1,209 @@
+//! Cursor-based pagination over the entries under a prefix.
+//!
+//! A [`Cursor`] records the last key of the previous page and the direction of the listing,
+//! followed by a tag computed with a key only known to the nodes running the nucleus, derived
+//! like the keys of a [`SealedMap`](super::sealed::SealedMap). The tag covers the prefix of the
+//! listing too, so a cursor which was forged, modified or issued for another listing is rejected
+//! with `RuntimeError::InvalidCursor`. The cursor is opaque to the clients but not encrypted.
+//!
+//! Issuing and verifying cursors needs the length-aware host, with the `legacy-host` feature only
+//! the first page can be listed.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::{Cursor, Direction, Page, StorageMap};
+//! use vrs_core_sdk::{get, CallResult};
+//!
+//! #[derive(Debug, Decode, Encode)]
+//! #[codec(crate = vrs_core_sdk::codec)]
+//! pub struct User {
+//!     pub name: String,
+//! }
+//!
+//! const USERS: StorageMap<u64, User> = StorageMap::new(b"user:");
+//!
+//! #[get]
+//! pub fn list_users(cursor: Option<Cursor>) -> CallResult<Page<(u64, User)>> {
+//!     USERS.page(cursor.as_ref(), 20, Direction::Forward)
+//! }
+//! ```
+//!
+//! With the `mock` feature, pages of [`MAX_RANGE_LIMIT`] entries resumed from a cursor list
+//! every entry in both directions, and cursors which don't match the listing are rejected:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::{self, Cursor, Direction, MAX_RANGE_LIMIT};
+//! use vrs_core_sdk::{error::RuntimeError, mock};
+//!
+//! mock::reset();
+//! for i in 0..2500u32 {
+//!     storage::put([b"item:".as_ref(), &i.to_be_bytes()].concat(), []).unwrap();
+//! }
+//! for direction in [Direction::Forward, Direction::Reverse] {
+//!     let (mut keys, mut cursor) = (vec![], None);
+//!     loop {
+//!         let page = storage::list_page(b"item:", cursor.as_ref(), MAX_RANGE_LIMIT, direction)
+//!             .unwrap();
+//!         keys.extend(page.items.into_iter().map(|(k, _)| k));
+//!         match page.next_cursor {
+//!             Some(next) => cursor = Some(next),
+//!             None => break,
+//!         }
+//!     }
+//!     let mut expected: Vec<_> = (0..2500u32)
+//!         .map(|i| [b"item:".as_ref(), &i.to_be_bytes()].concat())
+//!         .collect();
+//!     if direction == Direction::Reverse {
+//!         expected.reverse();
+//!     }
+//!     assert_eq!(keys, expected);
+//! }
+//!
+//! let page = storage::list_page(b"item:", None, 10, Direction::Forward).unwrap();
+//! assert!(matches!(
+//!     storage::list_page(b"item:", page.next_cursor.as_ref(), 10, Direction::Reverse),
+//!     Err(RuntimeError::InvalidCursor)
+//! ));
+//!
+//! // a modified cursor or one issued for another prefix is rejected
+//! let mut raw = page.next_cursor.encode();
+//! *raw.last_mut().unwrap() ^= 1;
+//! let tampered = Option::<Cursor>::decode(&mut &raw[..]).unwrap();
+//! assert!(matches!(
+//!     storage::list_page(b"item:", tampered.as_ref(), 10, Direction::Forward),
+//!     Err(RuntimeError::InvalidCursor)
+//! ));
+//! assert!(matches!(
+//!     storage::list_page(b"item", page.next_cursor.as_ref(), 10, Direction::Forward),
+//!     Err(RuntimeError::InvalidCursor)
+//! ));
+//!
+//! // the keys under a prefix of `0xff` bytes can't be listed in reverse
+//! assert!(storage::list_page([0xff, 0xff], None, 10, Direction::Reverse).is_err());
+//! # }
+//! ```
+
+use super::{prefix_start, scan_prefix, sealed::sealing_key, Direction, MAX_RANGE_LIMIT};
+use crate::{error::RuntimeError, CallResult};
+use codec::{Decode, DecodeAll, Encode};
+use scale_info::TypeInfo;
+
+/// A page of items and the cursor of the next page, `None` if this is the last page.
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, TypeInfo)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<Cursor>,
+}
+
+impl<T> Page<T> {
+    /// Convert the items of the page, keeping the cursor.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// An opaque continuation token returned by [`list_page`].
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, TypeInfo)]
+pub struct Cursor(Vec<u8>);
+
+#[derive(Encode, Decode)]
+struct CursorPayload {
+    last_key: Vec<u8>,
+    reverse: bool,
+}
+
+/// The namespace of the sealing key authenticating the cursors.
+const CURSOR_NAMESPACE: &[u8] = b"__vrs:cursor";
+const CURSOR_TAG_LEN: usize = 16;
+
+/// A keyed blake2 hash of the listing prefix and the payload, blake2 isn't subject to length
+/// extension so prepending the key makes it a MAC.
+fn cursor_tag(key: &[u8; 32], prefix: &[u8], payload: &[u8]) -> [u8; CURSOR_TAG_LEN] {
+    sp_crypto_hashing::blake2_128(&(key, prefix, payload).encode())
+}
+
+impl Cursor {
+    fn issue(key: &[u8; 32], prefix: &[u8], last_key: Vec<u8>, direction: Direction) -> Self {
+        let mut raw = CursorPayload {
+            last_key,
+            reverse: direction == Direction::Reverse,
+        }
+        .encode();
+        let tag = cursor_tag(key, prefix, &raw);
+        raw.extend_from_slice(&tag);
+        Self(raw)
+    }
+
+    /// Verify the cursor against the listing and return the last key of the previous page.
+    fn verify(&self, key: &[u8; 32], prefix: &[u8], direction: Direction) -> CallResult<Vec<u8>> {
+        let payload_len = self
+            .0
+            .len()
+            .checked_sub(CURSOR_TAG_LEN)
+            .ok_or(RuntimeError::InvalidCursor)?;
+        let (raw, tag) = self.0.split_at(payload_len);
+        if cursor_tag(key, prefix, raw) != tag {
+            return Err(RuntimeError::InvalidCursor);
+        }
+        let payload =
+            CursorPayload::decode_all(&mut &raw[..]).map_err(|_| RuntimeError::InvalidCursor)?;
+        if payload.reverse != (direction == Direction::Reverse)
+            || !payload.last_key.starts_with(prefix)
+        {
+            return Err(RuntimeError::InvalidCursor);
+        }
+        Ok(payload.last_key)
+    }
+}
+
+/// List at most `limit` entries under `prefix`, continuing from `cursor` if provided.
+pub fn list_page(
+    prefix: impl AsRef<[u8]>,
+    cursor: Option<&Cursor>,
+    limit: usize,
+    direction: Direction,
+) -> CallResult<Page<(Vec<u8>, Vec<u8>)>> {
+    let prefix = prefix.as_ref();
+    if limit == 0 || limit > MAX_RANGE_LIMIT {
+        return Err(RuntimeError::LimitExceeded);
+    }
+    let (start, exclusive) = match cursor {
+        Some(cursor) => (
+            cursor.verify(&sealing_key(CURSOR_NAMESPACE, 0)?, prefix, direction)?,
+            true,
+        ),
+        None => (prefix_start(prefix, direction)?, false),
+    };
+    // read one more entry to find out whether there is a next page
+    let fetch = (limit + 1).min(MAX_RANGE_LIMIT);
+    let mut items = scan_prefix(prefix, &start, exclusive, direction, fetch)?;
+    let has_more = if items.len() > limit {
+        items.truncate(limit);
+        true
+    } else {
+        // the host read is capped, so it may have stopped short of the extra entry, or even of
+        // the limit when the key of the cursor was skipped
+        match items.last() {
+            Some((last_key, _)) if fetch == MAX_RANGE_LIMIT => {
+                !scan_prefix(prefix, last_key, true, direction, 1)?.is_empty()
+            }
+            _ => false,
+        }
+    };
+    let next_cursor = match items.last() {
+        Some((last_key, _)) if has_more => {
+            let key = sealing_key(CURSOR_NAMESPACE, 0)?;
+            Some(Cursor::issue(&key, prefix, last_key.clone(), direction))
+        }
+        _ => None,
+    };
+    Ok(Page { items, next_cursor })
+}
@@ -0,0 +1,308 @@
+//! Merkle proofs of the kv storage.
+//!
+//! The state root of a nucleus commits to all its entries ordered by key. Each entry is a leaf
+//! of a binary merkle tree built like RFC 6962 with blake2-256, a leaf hashes
+//! `0x00 ++ SCALE((key, value))` and an inner node hashes `0x01 ++ left ++ right`, so a proof
+//! of one entry holds `log2(n)` hashes.
+//!
+//! This tree is a commitment defined by the SDK and computed by the host on request, it is not
+//! the state commitment the node submits to the Verisense chain, whose format is internal to
+//! the host. A root returned by [`state_root`](super::state_root) is therefore only as
+//! trustworthy as the node serving it, e.g. it can be compared across the nodes of the subnet
+//! or signed by the nucleus. [`verify_proof`] is pure Rust and can be used off-chain to check a
+//! value returned with [`prove`](super::prove) against such a root.
+//!
+//! The same tree commits to the leaves of a [`MerkleLog`](super::MerkleLog), which also
+//! provides [`ConsistencyProof`]s between two sizes of the log.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::storage::{self, proof::Hash, Proof};
+//! use vrs_core_sdk::{get, CallResult};
+//!
+//! #[get]
+//! pub fn balance_with_proof(user: String) -> CallResult<(Hash, Vec<u8>, Proof)> {
+//!     let key = format!("balance:{}", user);
+//!     let value = storage::get(&key)?.unwrap_or_default();
+//!     Ok((storage::state_root()?, value, storage::prove(&key)?))
+//! }
+//! ```
+//!
+//! With the `mock` feature:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::{mock, storage};
+//!
+//! mock::reset();
+//! storage::put(b"alice", b"10").unwrap();
+//! storage::put(b"bob", b"20").unwrap();
+//! storage::put(b"carol", b"30").unwrap();
+//! let proof = storage::prove(b"bob").unwrap();
+//! let root = storage::state_root().unwrap();
+//! assert!(storage::verify_proof(&root, b"bob", b"20", &proof));
+//! assert!(!storage::verify_proof(&root, b"bob", b"21", &proof));
+//! # }
+//! ```
+
+use codec::{Decode, Encode};
+use core::convert::Infallible;
+use scale_info::TypeInfo;
+
+/// A blake2-256 hash.
+pub type Hash = [u8; 32];
+
+/// The proof of an entry in the kv storage.
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, TypeInfo)]
+pub struct Proof {
+    /// the position of the entry among all entries ordered by key
+    pub leaf_index: u64,
+    /// the number of entries when the proof was made
+    pub leaf_count: u64,
+    /// the sibling hashes from the leaf up to the root
+    pub path: Vec<Hash>,
+}
+
+/// The proof that a tree of `old_size` leaves is a prefix of a tree of `new_size` leaves.
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, TypeInfo)]
+pub struct ConsistencyProof {
+    pub old_size: u64,
+    pub new_size: u64,
+    pub path: Vec<Hash>,
+}
+
+/// The hash of a leaf.
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    sp_crypto_hashing::blake2_256(&[&[0u8][..], data].concat())
+}
+
+/// The hash of an inner node.
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    sp_crypto_hashing::blake2_256(&[&[1u8][..], &left[..], &right[..]].concat())
+}
+
+/// The hash of the leaf of a storage entry.
+pub fn entry_hash(key: &[u8], value: &[u8]) -> Hash {
+    leaf_hash(&(key, value).encode())
+}
+
+/// The largest power of 2 which is smaller than `n`, `n` must be greater than 1.
+pub(crate) fn split(n: u64) -> u64 {
+    1 << (u64::BITS - (n - 1).leading_zeros() - 1)
+}
+
+/// The root of the tree of `leaves`, the hash of nothing if there is no leaf.
+pub fn root(leaves: &[Hash]) -> Hash {
+    match leaves.len() {
+        0 => sp_crypto_hashing::blake2_256(&[]),
+        1 => leaves[0],
+        n => {
+            let k = split(n as u64) as usize;
+            node_hash(&root(&leaves[..k]), &root(&leaves[k..]))
+        }
+    }
+}
+
+/// The inclusion path of the leaf at `index` in the `size` leaves from `start`, `subtree(start,
+/// size)` returns the root of a subtree.
+pub(crate) fn inclusion_path_with<E>(
+    index: u64,
+    start: u64,
+    size: u64,
+    subtree: &mut impl FnMut(u64, u64) -> Result<Hash, E>,
+) -> Result<Vec<Hash>, E> {
+    if size <= 1 {
+        return Ok(vec![]);
+    }
+    let k = split(size);
+    let (mut path, sibling) = if index < k {
+        (
+            inclusion_path_with(index, start, k, subtree)?,
+            subtree(start + k, size - k)?,
+        )
+    } else {
+        (
+            inclusion_path_with(index - k, start + k, size - k, subtree)?,
+            subtree(start, k)?,
+        )
+    };
+    path.push(sibling);
+    Ok(path)
+}
+
+/// The consistency path between the first `old_size` leaves and the `size` leaves from `start`
+/// as defined by RFC 6962, `complete` is set while the old tree is a complete subtree so far.
+pub(crate) fn consistency_path_with<E>(
+    old_size: u64,
+    start: u64,
+    size: u64,
+    complete: bool,
+    subtree: &mut impl FnMut(u64, u64) -> Result<Hash, E>,
+) -> Result<Vec<Hash>, E> {
+    if old_size == size {
+        return Ok(if complete {
+            vec![]
+        } else {
+            vec![subtree(start, size)?]
+        });
+    }
+    let k = split(size);
+    let (mut path, sibling) = if old_size <= k {
+        (
+            consistency_path_with(old_size, start, k, complete, subtree)?,
+            subtree(start + k, size - k)?,
+        )
+    } else {
+        (
+            consistency_path_with(old_size - k, start + k, size - k, false, subtree)?,
+            subtree(start, k)?,
+        )
+    };
+    path.push(sibling);
+    Ok(path)
+}
+
+fn slice_subtree(leaves: &[Hash]) -> impl FnMut(u64, u64) -> Result<Hash, Infallible> + '_ {
+    |start, size| Ok(root(&leaves[start as usize..(start + size) as usize]))
+}
+
+/// The inclusion path of the leaf at `index`, `index` must be in `leaves`.
+pub fn inclusion_path(leaves: &[Hash], index: usize) -> Vec<Hash> {
+    let Ok(path) = inclusion_path_with(
+        index as u64,
+        0,
+        leaves.len() as u64,
+        &mut slice_subtree(leaves),
+    );
+    path
+}
+
+/// The proof that the tree of the first `old_size` of `leaves` is a prefix of the tree of all
+/// `leaves`, `old_size` must not be greater than the number of leaves.
+pub fn consistency_path(leaves: &[Hash], old_size: usize) -> Vec<Hash> {
+    if old_size == 0 {
+        return vec![];
+    }
+    let Ok(path) = consistency_path_with(
+        old_size as u64,
+        0,
+        leaves.len() as u64,
+        true,
+        &mut slice_subtree(leaves),
+    );
+    path
+}
+
+/// Verify that `leaf` is at `index` of a tree of `size` leaves with the `root`.
+pub fn verify_inclusion(root: &Hash, leaf: Hash, index: u64, size: u64, path: &[Hash]) -> bool {
+    if index >= size {
+        return false;
+    }
+    let (mut node, mut last) = (index, size - 1);
+    let mut r = leaf;
+    for p in path {
+        if last == 0 {
+            return false;
+        }
+        if node & 1 == 1 || node == last {
+            r = node_hash(p, &r);
+            while node & 1 == 0 && node != 0 {
+                node >>= 1;
+                last >>= 1;
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        node >>= 1;
+        last >>= 1;
+    }
+    last == 0 && &r == root
+}
+
+/// Verify that the tree with `old_root` is a prefix of the tree with `new_root`.
+///
+/// ```
+/// use vrs_core_sdk::storage::proof::*;
+///
+/// let leaves: Vec<_> = (0u8..7).map(|i| leaf_hash(&[i])).collect();
+/// let proof = ConsistencyProof {
+///     old_size: 3,
+///     new_size: 7,
+///     path: consistency_path(&leaves, 3),
+/// };
+/// assert!(verify_consistency(&root(&leaves[..3]), &root(&leaves), &proof));
+/// assert!(!verify_consistency(&root(&leaves[..2]), &root(&leaves), &proof));
+/// ```
+pub fn verify_consistency(old_root: &Hash, new_root: &Hash, proof: &ConsistencyProof) -> bool {
+    let (old_size, new_size, path) = (proof.old_size, proof.new_size, &proof.path[..]);
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return path.is_empty() && old_root == new_root;
+    }
+    if old_size == 0 {
+        // every tree extends the empty tree
+        return path.is_empty();
+    }
+    // the old root is the first node of the path unless it is a complete subtree
+    let path = if old_size.is_power_of_two() {
+        [&[*old_root][..], path].concat()
+    } else {
+        path.to_vec()
+    };
+    let Some((first, rest)) = path.split_first() else {
+        return false;
+    };
+    let (mut node, mut last) = (old_size - 1, new_size - 1);
+    while node & 1 == 1 {
+        node >>= 1;
+        last >>= 1;
+    }
+    let (mut old, mut new) = (*first, *first);
+    for p in rest {
+        if last == 0 {
+            return false;
+        }
+        if node & 1 == 1 || node == last {
+            old = node_hash(p, &old);
+            new = node_hash(p, &new);
+            while node & 1 == 0 && node != 0 {
+                node >>= 1;
+                last >>= 1;
+            }
+        } else {
+            new = node_hash(&new, p);
+        }
+        node >>= 1;
+        last >>= 1;
+    }
+    last == 0 && &old == old_root && &new == new_root
+}
+
+/// Verify that `key` holds `value` in the storage with the state `root`.
+///
+/// ```
+/// use vrs_core_sdk::storage::proof::{entry_hash, inclusion_path, root, verify_proof, Proof};
+///
+/// let entries = [(b"a", b"1"), (b"b", b"2"), (b"c", b"3")];
+/// let leaves: Vec<_> = entries.iter().map(|(k, v)| entry_hash(*k, *v)).collect();
+/// let proof = Proof {
+///     leaf_index: 1,
+///     leaf_count: 3,
+///     path: inclusion_path(&leaves, 1),
+/// };
+/// assert!(verify_proof(&root(&leaves), b"b", b"2", &proof));
+/// assert!(!verify_proof(&root(&leaves), b"b", b"3", &proof));
+/// ```
+pub fn verify_proof(root: &Hash, key: &[u8], value: &[u8], proof: &Proof) -> bool {
+    verify_inclusion(
+        root,
+        entry_hash(key, value),
+        proof.leaf_index,
+        proof.leaf_count,
+        &proof.path,
+    )
+}
@@ -0,0 +1,315 @@
+//! FIFO queues and double-ended queues stored under a prefix.
+//!
+//! The items are stored under `prefix ++ "i" ++ index` between a head and a tail counter, stored
+//! under `prefix ++ "h"` and `prefix ++ "t"`. Pushing allocates the index with
+//! [`increment`] and popping claims the index with
+//! [`compare_and_swap`], so two calls never push to or pop from the
+//! same slot.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::Queue;
+//! use vrs_core_sdk::{get, post, CallResult};
+//!
+//! #[derive(Debug, Decode, Encode)]
+//! #[codec(crate = vrs_core_sdk::codec)]
+//! pub struct Webhook {
+//!     pub url: String,
+//!     pub body: Vec<u8>,
+//! }
+//!
+//! const OUTBOX: Queue<Webhook> = Queue::new(b"outbox:");
+//!
+//! #[post]
+//! pub fn enqueue(webhook: Webhook) -> CallResult<()> {
+//!     OUTBOX.push(&webhook)
+//! }
+//!
+//! #[post]
+//! pub fn dequeue() -> CallResult<Option<Webhook>> {
+//!     OUTBOX.pop()
+//! }
+//!
+//! #[get]
+//! pub fn pending(limit: u32) -> CallResult<Vec<Webhook>> {
+//!     OUTBOX.iter(limit as usize)
+//! }
+//! ```
+//!
+//! With the `mock` feature, a [`Deque`] is used from both ends:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::{mock, storage::Deque};
+//!
+//! const JOBS: Deque<u32> = Deque::new(b"jobs:");
+//!
+//! mock::reset();
+//! JOBS.push_back(&2).unwrap();
+//! JOBS.push_back(&3).unwrap();
+//! JOBS.push_front(&1).unwrap();
+//! assert_eq!(JOBS.len().unwrap(), 3);
+//! assert_eq!(JOBS.iter(10).unwrap(), vec![1, 2, 3]);
+//! assert_eq!(JOBS.pop_back().unwrap(), Some(3));
+//! assert_eq!(JOBS.pop_front().unwrap(), Some(1));
+//! assert_eq!(JOBS.peek_front().unwrap(), Some(2));
+//! assert_eq!(JOBS.pop_front().unwrap(), Some(2));
+//! assert_eq!(JOBS.pop_front().unwrap(), None);
+//! # }
+//! ```
+
+use super::{
+    compare_and_swap,
+    compress::{decode_stored, encode_stored, Compression},
+    decode_value, get, get_range, increment,
+    key::KeyEncode,
+    put, take, Direction,
+};
+use crate::{
+    abi::{ApiRegistry, StorageKind, StorageLayout},
+    CallResult,
+};
+use codec::{Decode, Encode};
+use scale_info::{meta_type, TypeInfo};
+use std::marker::PhantomData;
+
+/// A double-ended queue, see the [module](self) documentation.
+pub struct Deque<T> {
+    prefix: &'static [u8],
+    compression: Option<Compression>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Clone, Copy)]
+enum End {
+    Front,
+    Back,
+}
+
+impl<T> Deque<T> {
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self {
+            prefix,
+            compression: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Compress the items, see [`compress`](super::compress).
+    pub const fn with_compression(self, compression: Compression) -> Self {
+        Self {
+            compression: Some(compression),
+            ..self
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    fn counter_key(&self, end: End) -> Vec<u8> {
+        let tag: &[u8] = match end {
+            End::Front => b"h",
+            End::Back => b"t",
+        };
+        [self.prefix, tag].concat()
+    }
+
+    fn items_prefix(&self) -> Vec<u8> {
+        [self.prefix, b"i"].concat()
+    }
+
+    fn item_key(&self, index: i64) -> Vec<u8> {
+        let mut key = self.items_prefix();
+        index.encode_key_to(&mut key);
+        key
+    }
+
+    /// The raw value and the value of a counter, which starts from `0`.
+    fn counter(&self, end: End) -> CallResult<(Option<Vec<u8>>, i64)> {
+        let key = self.counter_key(end);
+        let raw = get(&key)?;
+        let value = match &raw {
+            Some(v) => decode_value(&key, v)?,
+            None => 0,
+        };
+        Ok((raw, value))
+    }
+
+    fn bounds(&self) -> CallResult<(i64, i64)> {
+        Ok((self.counter(End::Front)?.1, self.counter(End::Back)?.1))
+    }
+
+    /// The number of items in the queue.
+    pub fn len(&self) -> CallResult<u64> {
+        let (head, tail) = self.bounds()?;
+        Ok(tail.saturating_sub(head).max(0) as u64)
+    }
+
+    pub fn is_empty(&self) -> CallResult<bool> {
+        self.len().map(|len| len == 0)
+    }
+}
+
+impl<T: TypeInfo + 'static> StorageLayout for Deque<T> {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry) {
+        registry.register_storage(
+            name.to_string(),
+            StorageKind::Deque {
+                compressed: self.compression.is_some(),
+            },
+            self.prefix,
+            meta_type::<i64>(),
+            meta_type::<T>(),
+        );
+    }
+}
+
+impl<T: Encode + Decode> Deque<T> {
+    pub fn push_back(&self, item: &T) -> CallResult<()> {
+        let index = increment(self.counter_key(End::Back), 1)? - 1;
+        put(
+            self.item_key(index),
+            encode_stored(self.compression.as_ref(), item),
+        )
+    }
+
+    pub fn push_front(&self, item: &T) -> CallResult<()> {
+        let index = increment(self.counter_key(End::Front), -1)?;
+        put(
+            self.item_key(index),
+            encode_stored(self.compression.as_ref(), item),
+        )
+    }
+
+    pub fn pop_front(&self) -> CallResult<Option<T>> {
+        self.pop(End::Front)
+    }
+
+    pub fn pop_back(&self) -> CallResult<Option<T>> {
+        self.pop(End::Back)
+    }
+
+    pub fn peek_front(&self) -> CallResult<Option<T>> {
+        let (head, tail) = self.bounds()?;
+        if head >= tail {
+            return Ok(None);
+        }
+        self.get(head)
+    }
+
+    pub fn peek_back(&self) -> CallResult<Option<T>> {
+        let (head, tail) = self.bounds()?;
+        if head >= tail {
+            return Ok(None);
+        }
+        self.get(tail - 1)
+    }
+
+    /// Get at most `limit` items from the front, the limit maximum is `MAX_RANGE_LIMIT`.
+    pub fn iter(&self, limit: usize) -> CallResult<Vec<T>> {
+        let (head, tail) = self.bounds()?;
+        let limit = limit.min(tail.saturating_sub(head).max(0) as usize);
+        let items_prefix = self.items_prefix();
+        get_range(self.item_key(head), Direction::Forward, limit)?
+            .into_iter()
+            .take_while(|(k, _)| k.starts_with(&items_prefix))
+            .map(|(k, v)| decode_stored(self.compression.as_ref(), &k, &v))
+            .collect()
+    }
+
+    fn get(&self, index: i64) -> CallResult<Option<T>> {
+        let key = self.item_key(index);
+        get(&key)?
+            .map(|v| decode_stored(self.compression.as_ref(), &key, &v))
+            .transpose()
+    }
+
+    fn pop(&self, end: End) -> CallResult<Option<T>> {
+        loop {
+            let (raw, current) = self.counter(end)?;
+            let (head, tail) = match end {
+                End::Front => (current, self.counter(End::Back)?.1),
+                End::Back => (self.counter(End::Front)?.1, current),
+            };
+            if head >= tail {
+                return Ok(None);
+            }
+            let (index, next) = match end {
+                End::Front => (head, head + 1),
+                End::Back => (tail - 1, tail - 1),
+            };
+            // another call may have moved the counter since it was read, retry in that case
+            if compare_and_swap(self.counter_key(end), raw.as_deref(), Some(&next.encode()))? {
+                let key = self.item_key(index);
+                return take(&key)?
+                    .map(|v| decode_stored(self.compression.as_ref(), &key, &v))
+                    .transpose();
+            }
+        }
+    }
+}
+
+/// A FIFO queue, see the [module](self) documentation.
+pub struct Queue<T> {
+    inner: Deque<T>,
+}
+
+impl<T> Queue<T> {
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self {
+            inner: Deque::new(prefix),
+        }
+    }
+
+    /// Compress the items, see [`compress`](super::compress).
+    pub const fn with_compression(self, compression: Compression) -> Self {
+        Self {
+            inner: self.inner.with_compression(compression),
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.inner.prefix()
+    }
+
+    /// The number of items in the queue.
+    pub fn len(&self) -> CallResult<u64> {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> CallResult<bool> {
+        self.inner.is_empty()
+    }
+}
+
+impl<T: TypeInfo + 'static> StorageLayout for Queue<T> {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry) {
+        self.inner.register_storage(name, registry)
+    }
+}
+
+impl<T: Encode + Decode> Queue<T> {
+    /// Append an item to the back of the queue.
+    pub fn push(&self, item: &T) -> CallResult<()> {
+        self.inner.push_back(item)
+    }
+
+    /// Remove the item at the front of the queue.
+    pub fn pop(&self) -> CallResult<Option<T>> {
+        self.inner.pop_front()
+    }
+
+    /// Get the item at the front of the queue without removing it.
+    pub fn peek(&self) -> CallResult<Option<T>> {
+        self.inner.peek_front()
+    }
+
+    /// Get at most `limit` items from the front, the limit maximum is `MAX_RANGE_LIMIT`.
+    pub fn iter(&self, limit: usize) -> CallResult<Vec<T>> {
+        self.inner.iter(limit)
+    }
+}
@@ -0,0 +1,114 @@
+//! Storage usage accounting and soft quotas of typed containers.
+//!
+//! The usage of the whole storage and of any prefix is reported by the host, see
+//! [`usage`](super::usage) and [`prefix_usage`](super::prefix_usage). A [`StorageMap`] created
+//! with a [`Quota`] checks the usage of its prefix before growing and fails with
+//! `RuntimeError::QuotaExceeded` instead of writing. The quota is enforced by the SDK, entries
+//! written under the prefix by other means aren't checked but still count.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::codec::{Decode, Encode};
+//! use vrs_core_sdk::storage::{Quota, StorageMap, Usage};
+//! use vrs_core_sdk::{get, post, CallResult};
+//!
+//! #[derive(Debug, Decode, Encode)]
+//! #[codec(crate = vrs_core_sdk::codec)]
+//! pub struct Comment {
+//!     pub text: String,
+//! }
+//!
+//! const COMMENTS: StorageMap<u64, Comment> =
+//!     StorageMap::new(b"comment:").with_quota(Quota::new().max_keys(10_000).max_bytes(1 << 20));
+//!
+//! #[post]
+//! pub fn add_comment(comment: Comment) -> CallResult<u64> {
+//!     COMMENTS.insert_auto(&comment)
+//! }
+//!
+//! #[get]
+//! pub fn comments_usage() -> CallResult<Usage> {
+//!     COMMENTS.usage()
+//! }
+//! ```
+//!
+//! With the `mock` feature, replacing an entry doesn't count as a new key:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::storage::{Quota, StorageMap};
+//! use vrs_core_sdk::{error::RuntimeError, mock};
+//!
+//! const NAMES: StorageMap<u64, String> = StorageMap::new(b"name:").with_quota(Quota::new().max_keys(1));
+//!
+//! mock::reset();
+//! NAMES.insert(&1, &"alice".to_string()).unwrap();
+//! NAMES.insert(&1, &"bob".to_string()).unwrap();
+//! assert!(matches!(NAMES.insert(&2, &"carol".to_string()), Err(RuntimeError::QuotaExceeded)));
+//! assert_eq!(NAMES.get(&2).unwrap(), None);
+//! assert_eq!(NAMES.usage().unwrap().keys, 1);
+//! # }
+//! ```
+//!
+//! [`StorageMap`]: super::StorageMap
+
+use super::prefix_usage;
+use crate::{error::RuntimeError, CallResult};
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+
+/// The number of keys and the total bytes of keys and values.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Encode, Decode, TypeInfo)]
+pub struct Usage {
+    pub keys: u64,
+    pub bytes: u64,
+}
+
+/// The limits of a typed container, no limit is set by default.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Quota {
+    pub max_keys: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+impl Quota {
+    pub const fn new() -> Self {
+        Self {
+            max_keys: None,
+            max_bytes: None,
+        }
+    }
+
+    pub const fn max_keys(self, max_keys: u64) -> Self {
+        Self {
+            max_keys: Some(max_keys),
+            ..self
+        }
+    }
+
+    pub const fn max_bytes(self, max_bytes: u64) -> Self {
+        Self {
+            max_bytes: Some(max_bytes),
+            ..self
+        }
+    }
+
+    /// Check that `prefix` can grow by `keys` keys and `bytes` bytes.
+    pub(crate) fn check(&self, prefix: &[u8], keys: u64, bytes: i64) -> CallResult<()> {
+        if keys == 0 && bytes <= 0 {
+            return Ok(());
+        }
+        let usage = prefix_usage(prefix)?;
+        let exceeds = |max: Option<u64>, used: u64, added: u64| {
+            max.is_some_and(|max| used.saturating_add(added) > max)
+        };
+        if exceeds(self.max_keys, usage.keys, keys)
+            || exceeds(self.max_bytes, usage.bytes, bytes.max(0) as u64)
+        {
+            return Err(RuntimeError::QuotaExceeded);
+        }
+        Ok(())
+    }
+}
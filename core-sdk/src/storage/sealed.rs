@@ -0,0 +1,270 @@
+//! Values encrypted at rest with a sealing key of the nucleus.
+//!
+//! The kv storage is replicated to every node of the subnet, so a [`SealedMap`] encrypts its
+//! values with ChaCha20-Poly1305 before writing them. The key is derived by the host for the
+//! nucleus, the prefix of the map (the namespace) and a key version, and never leaves the
+//! nodes running the nucleus. It is not derived from `tss_sign` since threshold signatures
+//! aren't deterministic and end up public.
+//!
+//! Each value is stored as SCALE `(version, nonce, ciphertext)`, where the nonce comes from a
+//! counter of the namespace and the storage key is authenticated along with the value, so a
+//! value copied under another key fails to decrypt.
+//!
+//! [`SealedMap::rotate_key`] switches new writes to the next key version, the existing values
+//! stay readable and are re-encrypted in batches with [`SealedMap::reencrypt`].
+//!
+//! The sealing key is only provided by the length-aware host, a [`SealedMap`] fails with
+//! `RuntimeError::UnexpectedHostStatus` under the `legacy-host` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::storage::{sealed::SealedMap, Cursor};
+//! use vrs_core_sdk::{get, post, CallResult};
+//!
+//! const API_KEYS: SealedMap<String, String> = SealedMap::new(b"api_key:");
+//!
+//! #[post]
+//! pub fn set_api_key(service: String, key: String) -> CallResult<()> {
+//!     API_KEYS.insert(&service, &key)
+//! }
+//!
+//! #[post]
+//! pub fn rotate() -> CallResult<u32> {
+//!     API_KEYS.rotate_key()
+//! }
+//!
+//! #[post]
+//! pub fn reencrypt(cursor: Option<Cursor>) -> CallResult<Option<Cursor>> {
+//!     API_KEYS.reencrypt(cursor.as_ref(), 100)
+//! }
+//! ```
+//!
+//! With the `mock` feature, the values stay readable across a key rotation:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::storage::{self, sealed::SealedMap};
+//! use vrs_core_sdk::mock;
+//!
+//! const SECRETS: SealedMap<u32, String> = SealedMap::new(b"secret:");
+//!
+//! mock::reset();
+//! SECRETS.insert(&1, &"hunter2".to_string()).unwrap();
+//! let stored = storage::get(SECRETS.storage_key(&1)).unwrap().unwrap();
+//! assert!(!stored.windows(7).any(|w| w == b"hunter2"));
+//! assert_eq!(SECRETS.rotate_key().unwrap(), 1);
+//! assert_eq!(SECRETS.reencrypt(None, 10).unwrap(), None);
+//! assert_ne!(storage::get(SECRETS.storage_key(&1)).unwrap().unwrap(), stored);
+//! assert_eq!(SECRETS.get(&1).unwrap(), Some("hunter2".to_string()));
+//! // a value copied under another key fails to decrypt
+//! storage::put(SECRETS.storage_key(&2), &stored).unwrap();
+//! assert!(SECRETS.get(&2).is_err());
+//! # }
+//! ```
+
+use super::{del, get, increment, key::KeyEncode, list_page, put, Cursor, Direction};
+use crate::{
+    abi::{ApiRegistry, StorageKind, StorageLayout},
+    error::RuntimeError,
+    CallResult,
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305,
+};
+use codec::{Decode, Encode};
+use scale_info::{meta_type, TypeInfo};
+use std::{collections::BTreeMap, marker::PhantomData};
+
+const VERSION_PREFIX: &[u8] = b"__vrs:sealed:v:";
+const NONCE_PREFIX: &[u8] = b"__vrs:sealed:n:";
+
+length_aware_host_fns! {
+    fn storage_sealing_key(
+        ns_ptr: *const u8,
+        ns_len: i32,
+        version: u32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+}
+
+/// Get the sealing key of the nucleus for `namespace` and `version`.
+pub(crate) fn sealing_key(namespace: &[u8], version: u32) -> CallResult<[u8; 32]> {
+    let ns_len = crate::check_len(namespace, RuntimeError::KeyTooLarge)?;
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        storage_sealing_key(namespace.as_ptr(), ns_len, version, ptr, cap, offset)
+    })?;
+    crate::decode_host_result(&buf)
+}
+
+#[derive(Encode, Decode)]
+struct SealedValue {
+    version: u32,
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+fn seal_error(key: &[u8]) -> RuntimeError {
+    RuntimeError::KvStorageError(format!(
+        "failed to unseal the value of 0x{}",
+        sp_core::hexdisplay::HexDisplay::from(&key)
+    ))
+}
+
+/// A map whose values are encrypted, see the [module](self) documentation.
+pub struct SealedMap<K, V> {
+    prefix: &'static [u8],
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> SealedMap<K, V> {
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self {
+            prefix,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    fn counter(&self, prefix: &[u8]) -> Vec<u8> {
+        [prefix, self.prefix].concat()
+    }
+
+    /// The version of the key used by new writes, starting from `0`.
+    pub fn key_version(&self) -> CallResult<u32> {
+        let key = self.counter(VERSION_PREFIX);
+        Ok(match get(&key)? {
+            Some(v) => super::decode_value::<i64>(&key, &v)? as u32,
+            None => 0,
+        })
+    }
+
+    /// Switch new writes to the next key version and return it.
+    pub fn rotate_key(&self) -> CallResult<u32> {
+        increment(self.counter(VERSION_PREFIX), 1).map(|version| version as u32)
+    }
+
+    fn cipher(&self, version: u32) -> CallResult<ChaCha20Poly1305> {
+        let key = sealing_key(self.prefix, version)?;
+        Ok(ChaCha20Poly1305::new(&key.into()))
+    }
+
+    fn seal(
+        &self,
+        cipher: &ChaCha20Poly1305,
+        version: u32,
+        key: &[u8],
+        value: &[u8],
+    ) -> CallResult<Vec<u8>> {
+        // the counter never repeats, so neither does the nonce under any key of the namespace
+        let counter = increment(self.counter(NONCE_PREFIX), 1)?;
+        let mut nonce = [0u8; 12];
+        nonce[..8].copy_from_slice(&counter.to_le_bytes());
+        let ciphertext = cipher
+            .encrypt(
+                &nonce.into(),
+                Payload {
+                    msg: value,
+                    aad: key,
+                },
+            )
+            .map_err(|_| seal_error(key))?;
+        Ok(SealedValue {
+            version,
+            nonce,
+            ciphertext,
+        }
+        .encode())
+    }
+
+    fn unseal(
+        &self,
+        ciphers: &mut BTreeMap<u32, ChaCha20Poly1305>,
+        key: &[u8],
+        stored: &[u8],
+    ) -> CallResult<(u32, Vec<u8>)> {
+        let sealed = SealedValue::decode(&mut &stored[..]).map_err(|_| seal_error(key))?;
+        let cipher = match ciphers.get(&sealed.version) {
+            Some(cipher) => cipher,
+            None => {
+                let cipher = self.cipher(sealed.version)?;
+                ciphers.entry(sealed.version).or_insert(cipher)
+            }
+        };
+        let value = cipher
+            .decrypt(
+                &sealed.nonce.into(),
+                Payload {
+                    msg: &sealed.ciphertext,
+                    aad: key,
+                },
+            )
+            .map_err(|_| seal_error(key))?;
+        Ok((sealed.version, value))
+    }
+
+    /// Re-encrypt at most `limit` entries with the current key version, continuing from
+    /// `cursor` if provided. Returns the cursor of the next batch, `None` once all entries have
+    /// been visited.
+    pub fn reencrypt(&self, cursor: Option<&Cursor>, limit: usize) -> CallResult<Option<Cursor>> {
+        let version = self.key_version()?;
+        let cipher = self.cipher(version)?;
+        let mut ciphers = BTreeMap::new();
+        let page = list_page(self.prefix, cursor, limit, Direction::Forward)?;
+        for (key, stored) in page.items {
+            let (old, value) = self.unseal(&mut ciphers, &key, &stored)?;
+            if old != version {
+                put(&key, self.seal(&cipher, version, &key, &value)?)?;
+            }
+        }
+        Ok(page.next_cursor)
+    }
+}
+
+impl<K: TypeInfo + 'static, V: TypeInfo + 'static> StorageLayout for SealedMap<K, V> {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry) {
+        registry.register_storage(
+            name.to_string(),
+            StorageKind::SealedMap,
+            self.prefix,
+            meta_type::<K>(),
+            meta_type::<V>(),
+        );
+    }
+}
+
+impl<K: KeyEncode, V: Encode + Decode> SealedMap<K, V> {
+    /// The raw storage key of `key`.
+    pub fn storage_key(&self, key: &K) -> Vec<u8> {
+        let mut raw = self.prefix.to_vec();
+        key.encode_key_to(&mut raw);
+        raw
+    }
+
+    pub fn get(&self, key: &K) -> CallResult<Option<V>> {
+        let key = self.storage_key(key);
+        let Some(stored) = get(&key)? else {
+            return Ok(None);
+        };
+        let (_, value) = self.unseal(&mut BTreeMap::new(), &key, &stored)?;
+        super::decode_value(&key, &value).map(Some)
+    }
+
+    pub fn insert(&self, key: &K, value: &V) -> CallResult<()> {
+        let key = self.storage_key(key);
+        let version = self.key_version()?;
+        let sealed = self.seal(&self.cipher(version)?, version, &key, &value.encode())?;
+        put(&key, sealed)
+    }
+
+    pub fn remove(&self, key: &K) -> CallResult<()> {
+        del(self.storage_key(key))
+    }
+}
@@ -0,0 +1,72 @@
+use super::{del, get, increment};
+use crate::{error::RuntimeError, CallResult};
+use codec::Decode;
+use std::borrow::Cow;
+
+/// An auto-increment sequence backed by a dedicated counter key, which is advanced with
+/// [`increment`] so concurrent calls never hand out the same id.
+///
+/// The first id handed out by a sequence is `1`.
+///
+/// # Examples
+///
+/// ```
+/// use vrs_core_sdk::{post, storage::Sequence};
+///
+/// const ORDER_ID: Sequence = Sequence::new(b"order_id");
+///
+/// #[post]
+/// pub fn new_order() -> Result<u64, String> {
+///     ORDER_ID.next().map_err(|e| e.to_string())
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Sequence {
+    key: Cow<'static, [u8]>,
+}
+
+impl Sequence {
+    pub const fn new(key: &'static [u8]) -> Self {
+        Self {
+            key: Cow::Borrowed(key),
+        }
+    }
+
+    pub(crate) fn with_key(key: Vec<u8>) -> Self {
+        Self {
+            key: Cow::Owned(key),
+        }
+    }
+
+    /// The key holding the counter of this sequence.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Advance the sequence and return the allocated id.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&self) -> CallResult<u64> {
+        increment(&self.key, 1).map(|id| id as u64)
+    }
+
+    /// Return the id the next call of `next` will allocate without advancing the sequence.
+    pub fn peek(&self) -> CallResult<u64> {
+        match get(&self.key)? {
+            Some(v) => {
+                let current = i64::decode(&mut &v[..]).map_err(|_| {
+                    RuntimeError::KvStorageError("invalid sequence counter".to_string())
+                })?;
+                current
+                    .checked_add(1)
+                    .map(|id| id as u64)
+                    .ok_or_else(|| RuntimeError::KvStorageError("sequence overflow".to_string()))
+            }
+            None => Ok(1),
+        }
+    }
+
+    /// Reset the sequence so the next allocated id is `1` again.
+    pub fn reset(&self) -> CallResult<()> {
+        del(&self.key)
+    }
+}
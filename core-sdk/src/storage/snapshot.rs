@@ -0,0 +1,223 @@
+//! Snapshots of the kv storage of a nucleus for local tooling, only available with `std`.
+//!
+//! A snapshot file starts with the magic bytes `VRSSNAP\0`, followed by the SCALE encoded
+//! header (format version, nucleus id and state root) and the entries ordered by key. The state
+//! root is computed as described in [`proof`] and checked when a snapshot is read,
+//! so a truncated or edited file is rejected.
+//!
+//! With the `mock` feature, `mock::load` replaces the mocked storage with a snapshot and
+//! `mock::snapshot` takes one, which can be compared with [`Snapshot::diff`].
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::storage::snapshot::{Change, Snapshot};
+//! use vrs_core_sdk::NucleusId;
+//!
+//! let id = NucleusId::from([0u8; 32]);
+//! let before = Snapshot::new(id.clone(), vec![(b"a".to_vec(), b"1".to_vec())]);
+//! let after = Snapshot::new(id, vec![(b"a".to_vec(), b"2".to_vec())]);
+//!
+//! let mut file = vec![];
+//! before.write_to(&mut file).unwrap();
+//! assert_eq!(Snapshot::read_from(&mut &file[..]).unwrap(), before);
+//! assert_eq!(before.entries().len(), 1);
+//! assert_eq!(
+//!     before.diff(&after),
+//!     vec![Change::Updated { key: b"a".to_vec(), old: b"1".to_vec(), new: b"2".to_vec() }]
+//! );
+//! ```
+//!
+//! With the `mock` feature:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::storage::{self, snapshot::Change};
+//! use vrs_core_sdk::mock;
+//!
+//! mock::reset();
+//! let before = mock::snapshot();
+//! storage::put(b"new", b"value").unwrap();
+//! let after = mock::snapshot();
+//! assert_eq!(
+//!     before.diff(&after),
+//!     vec![Change::Inserted { key: b"new".to_vec(), value: b"value".to_vec() }]
+//! );
+//! assert_eq!(after.state_root(), &storage::state_root().unwrap());
+//! mock::load(&before);
+//! assert_eq!(storage::get(b"new").unwrap(), None);
+//! # }
+//! ```
+
+use super::proof::{self, Hash};
+use crate::NucleusId;
+use codec::{Decode, Encode};
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+const MAGIC: &[u8; 8] = b"VRSSNAP\0";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Encode, Decode)]
+struct Header {
+    version: u32,
+    nucleus_id: NucleusId,
+    state_root: Hash,
+}
+
+/// The entries of the kv storage of a nucleus, whose state root always matches the entries.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Snapshot {
+    nucleus_id: NucleusId,
+    state_root: Hash,
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// A difference between two snapshots.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Change {
+    Inserted {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Removed {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Updated {
+        key: Vec<u8>,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    },
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn state_root(entries: &BTreeMap<Vec<u8>, Vec<u8>>) -> Hash {
+    let leaves = entries
+        .iter()
+        .map(|(k, v)| proof::entry_hash(k, v))
+        .collect::<Vec<_>>();
+    proof::root(&leaves)
+}
+
+impl Snapshot {
+    /// Create a snapshot of `entries`, computing its state root.
+    pub fn new(
+        nucleus_id: NucleusId,
+        entries: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    ) -> Self {
+        let entries = entries.into_iter().collect();
+        Self {
+            nucleus_id,
+            state_root: state_root(&entries),
+            entries,
+        }
+    }
+
+    pub fn nucleus_id(&self) -> &NucleusId {
+        &self.nucleus_id
+    }
+
+    pub fn state_root(&self) -> &Hash {
+        &self.state_root
+    }
+
+    pub fn entries(&self) -> &BTreeMap<Vec<u8>, Vec<u8>> {
+        &self.entries
+    }
+
+    pub fn into_entries(self) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        self.entries
+    }
+
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        let header = Header {
+            version: FORMAT_VERSION,
+            nucleus_id: self.nucleus_id.clone(),
+            state_root: self.state_root,
+        };
+        out.write_all(&header.encode())?;
+        out.write_all(&self.entries.encode())
+    }
+
+    pub fn read_from(input: &mut impl Read) -> io::Result<Self> {
+        let mut buf = vec![];
+        input.read_to_end(&mut buf)?;
+        let mut data = buf
+            .strip_prefix(&MAGIC[..])
+            .ok_or_else(|| invalid_data("not a storage snapshot"))?;
+        let header =
+            Header::decode(&mut data).map_err(|_| invalid_data("malformed snapshot header"))?;
+        if header.version != FORMAT_VERSION {
+            return Err(invalid_data("unsupported snapshot version"));
+        }
+        let entries = <BTreeMap<Vec<u8>, Vec<u8>> as codec::DecodeAll>::decode_all(&mut data)
+            .map_err(|_| invalid_data("malformed snapshot entries"))?;
+        if state_root(&entries) != header.state_root {
+            return Err(invalid_data("the entries don't match the state root"));
+        }
+        Ok(Self {
+            nucleus_id: header.nucleus_id,
+            state_root: header.state_root,
+            entries,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(&mut io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /// The changes turning this snapshot into `after`, ordered by key.
+    pub fn diff(&self, after: &Snapshot) -> Vec<Change> {
+        let mut changes = vec![];
+        let mut before = self.entries.iter().peekable();
+        let mut after = after.entries.iter().peekable();
+        loop {
+            let removed = match (before.peek(), after.peek()) {
+                (None, None) => break,
+                (Some((k0, _)), Some((k1, _))) if k0 == k1 => {
+                    let ((key, old), (_, new)) = (before.next().unwrap(), after.next().unwrap());
+                    if old != new {
+                        changes.push(Change::Updated {
+                            key: key.clone(),
+                            old: old.clone(),
+                            new: new.clone(),
+                        });
+                    }
+                    continue;
+                }
+                (Some((k0, _)), Some((k1, _))) => k0 < k1,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+            };
+            if removed {
+                let (key, value) = before.next().unwrap();
+                changes.push(Change::Removed {
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+            } else {
+                let (key, value) = after.next().unwrap();
+                changes.push(Change::Inserted {
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        changes
+    }
+}
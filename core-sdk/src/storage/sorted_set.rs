@@ -0,0 +1,235 @@
+//! A set of members ordered by score, e.g. a leaderboard.
+//!
+//! Each member is stored twice under the prefix of the set:
+//!
+//! - `prefix ++ "s" ++ score ++ member -> ()` orders the members by score, then by member;
+//! - `prefix ++ "m" ++ member -> score` finds the current score of a member.
+//!
+//! Both the scores and the members are encoded with [`KeyEncode`], so any integer type can be
+//! used as the score and the highest scores are read with `get_range` in `Direction::Reverse`.
+//!
+//! # Examples
+//!
+//! ```
+//! use vrs_core_sdk::storage::SortedSet;
+//! use vrs_core_sdk::{get, post, CallResult};
+//!
+//! const LEADERBOARD: SortedSet<String, u64> = SortedSet::new(b"leaderboard:");
+//!
+//! #[post]
+//! pub fn submit(player: String, score: u64) -> CallResult<()> {
+//!     LEADERBOARD.insert(&player, &score)
+//! }
+//!
+//! #[get]
+//! pub fn top10() -> CallResult<Vec<(String, u64)>> {
+//!     LEADERBOARD.top(10)
+//! }
+//! ```
+//!
+//! With the `mock` feature, inserting a member again replaces its score:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use vrs_core_sdk::{mock, storage::SortedSet};
+//!
+//! const SCORES: SortedSet<String, i32> = SortedSet::new(b"scores:");
+//!
+//! mock::reset();
+//! SCORES.insert(&"alice".to_string(), &10).unwrap();
+//! SCORES.insert(&"bob".to_string(), &-5).unwrap();
+//! SCORES.insert(&"carol".to_string(), &20).unwrap();
+//! SCORES.insert(&"alice".to_string(), &30).unwrap();
+//! assert_eq!(SCORES.len().unwrap(), 3);
+//! assert_eq!(SCORES.top(2).unwrap(), vec![("alice".to_string(), 30), ("carol".to_string(), 20)]);
+//! assert_eq!(SCORES.rank(&"bob".to_string()).unwrap(), Some(2));
+//! assert_eq!(SCORES.range_by_score(&-10, &20, 10).unwrap().len(), 2);
+//! assert!(SCORES.remove(&"carol".to_string()).unwrap());
+//! assert_eq!(SCORES.rank(&"bob".to_string()).unwrap(), Some(1));
+//! # }
+//! ```
+
+use super::{
+    del, get,
+    key::{KeyDecode, KeyEncode},
+    prefix_start, prefix_usage, put, scan_prefix, Direction, MAX_RANGE_LIMIT,
+};
+use crate::{
+    abi::{ApiRegistry, StorageKind, StorageLayout},
+    error::RuntimeError,
+    CallResult,
+};
+use scale_info::{meta_type, TypeInfo};
+use std::marker::PhantomData;
+
+/// A set of members ordered by score, see the [module](self) documentation.
+pub struct SortedSet<M, S> {
+    prefix: &'static [u8],
+    _marker: PhantomData<fn() -> (M, S)>,
+}
+
+impl<M, S> SortedSet<M, S> {
+    pub const fn new(prefix: &'static [u8]) -> Self {
+        Self {
+            prefix,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static [u8] {
+        self.prefix
+    }
+
+    fn scores_prefix(&self) -> Vec<u8> {
+        [self.prefix, b"s"].concat()
+    }
+
+    fn members_prefix(&self) -> Vec<u8> {
+        [self.prefix, b"m"].concat()
+    }
+
+    /// The number of members.
+    pub fn len(&self) -> CallResult<u64> {
+        prefix_usage(self.members_prefix()).map(|usage| usage.keys)
+    }
+
+    pub fn is_empty(&self) -> CallResult<bool> {
+        self.len().map(|len| len == 0)
+    }
+}
+
+impl<M: TypeInfo + 'static, S: TypeInfo + 'static> StorageLayout for SortedSet<M, S> {
+    fn register_storage(&self, name: &str, registry: &mut ApiRegistry) {
+        registry.register_storage(
+            name.to_string(),
+            StorageKind::SortedSet,
+            self.prefix,
+            meta_type::<M>(),
+            meta_type::<S>(),
+        );
+    }
+}
+
+fn malformed_entry(key: &[u8]) -> RuntimeError {
+    RuntimeError::KvStorageError(format!(
+        "malformed sorted set entry 0x{}",
+        sp_core::hexdisplay::HexDisplay::from(&key)
+    ))
+}
+
+impl<M, S> SortedSet<M, S>
+where
+    M: KeyEncode + KeyDecode,
+    S: KeyEncode + KeyDecode + PartialOrd,
+{
+    fn member_key(&self, member: &M) -> Vec<u8> {
+        let mut key = self.members_prefix();
+        member.encode_key_to(&mut key);
+        key
+    }
+
+    fn score_key(&self, member: &M, score: &S) -> Vec<u8> {
+        let mut key = self.scores_prefix();
+        score.encode_key_to(&mut key);
+        member.encode_key_to(&mut key);
+        key
+    }
+
+    fn decode_entry(&self, key: &[u8]) -> CallResult<(M, S)> {
+        let (score, member) = <(S, M)>::decode_key(&key[self.prefix.len() + 1..])
+            .map_err(|_| malformed_entry(key))?;
+        Ok((member, score))
+    }
+
+    /// Get the score of `member`.
+    pub fn score(&self, member: &M) -> CallResult<Option<S>> {
+        let key = self.member_key(member);
+        get(&key)?
+            .map(|v| S::decode_key(&v).map_err(|_| malformed_entry(&key)))
+            .transpose()
+    }
+
+    /// Insert `member` with `score`, replacing its previous score.
+    pub fn insert(&self, member: &M, score: &S) -> CallResult<()> {
+        if let Some(old) = self.score(member)? {
+            del(self.score_key(member, &old))?;
+        }
+        put(self.score_key(member, score), [])?;
+        put(self.member_key(member), score.encode_key())
+    }
+
+    /// Remove `member`, returning whether it was in the set.
+    pub fn remove(&self, member: &M) -> CallResult<bool> {
+        let Some(score) = self.score(member)? else {
+            return Ok(false);
+        };
+        del(self.score_key(member, &score))?;
+        del(self.member_key(member))?;
+        Ok(true)
+    }
+
+    /// The number of members ranked before `member` when ordered by descending score, i.e. `0`
+    /// for the highest score. Members with the same score are ordered by descending member.
+    ///
+    /// The members ranked before are counted by reading them in batches, so the cost grows
+    /// with the rank.
+    pub fn rank(&self, member: &M) -> CallResult<Option<u64>> {
+        let Some(score) = self.score(member)? else {
+            return Ok(None);
+        };
+        let target = self.score_key(member, &score);
+        let prefix = self.scores_prefix();
+        let mut start = prefix_start(&prefix, Direction::Reverse)?;
+        let mut exclusive = false;
+        let mut rank = 0;
+        loop {
+            let batch = scan_prefix(
+                &prefix,
+                &start,
+                exclusive,
+                Direction::Reverse,
+                MAX_RANGE_LIMIT - 1,
+            )?;
+            for (key, _) in &batch {
+                if key == &target {
+                    return Ok(Some(rank));
+                }
+                rank += 1;
+            }
+            match batch.into_iter().last() {
+                Some((key, _)) => start = key,
+                None => return Ok(None),
+            }
+            exclusive = true;
+        }
+    }
+
+    /// Get at most `limit` members with `min <= score <= max` in ascending order of score, the
+    /// limit maximum is `MAX_RANGE_LIMIT`.
+    pub fn range_by_score(&self, min: &S, max: &S, limit: usize) -> CallResult<Vec<(M, S)>> {
+        let prefix = self.scores_prefix();
+        let mut start = prefix.clone();
+        min.encode_key_to(&mut start);
+        let mut members = vec![];
+        for (key, _) in scan_prefix(&prefix, &start, false, Direction::Forward, limit)? {
+            let (member, score) = self.decode_entry(&key)?;
+            if score > *max {
+                break;
+            }
+            members.push((member, score));
+        }
+        Ok(members)
+    }
+
+    /// Get at most `n` members with the highest scores in descending order of score, the limit
+    /// maximum is `MAX_RANGE_LIMIT`.
+    pub fn top(&self, n: usize) -> CallResult<Vec<(M, S)>> {
+        let prefix = self.scores_prefix();
+        let start = prefix_start(&prefix, Direction::Reverse)?;
+        scan_prefix(&prefix, &start, false, Direction::Reverse, n)?
+            .into_iter()
+            .map(|(key, _)| self.decode_entry(&key))
+            .collect()
+    }
+}
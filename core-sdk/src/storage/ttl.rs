@@ -0,0 +1,198 @@
+//! Keys which expire after a time-to-live.
+//!
+//! [`put_with_ttl`] stores the value under its key as usual, along with its expiry time and an
+//! entry in an expiry index ordered by time. Expiry times are timestamps in milliseconds as
+//! returned by [`timer::now`]. [`get`] never returns an expired value, while
+//! the expired entries are actually removed by [`purge_expired`], e.g. from a timer.
+//!
+//! `storage::get` and `storage::put` don't know about the expiry, a key written with a TTL
+//! should be read with [`get`] and cleared with [`remove`] or [`persist`]. The expiry records the
+//! hash of the value it was set for, so a key overwritten by `storage::put` with another value
+//! no longer expires: [`get`] returns the new value and [`purge_expired`] only clears the stale
+//! expiry.
+//!
+//! # Examples
+//!
+//! ```
+//! # fn main() {}
+//! use std::time::Duration;
+//! use vrs_core_sdk::storage::ttl;
+//! use vrs_core_sdk::{get, init, post, set_timer, timer};
+//!
+//! #[init]
+//! pub fn init() {
+//!     set_timer!(Duration::from_secs(60), purge_sessions).unwrap();
+//! }
+//!
+//! #[timer]
+//! pub fn purge_sessions() {
+//!     let _ = ttl::purge_expired(100);
+//!     let _ = set_timer!(Duration::from_secs(60), purge_sessions);
+//! }
+//!
+//! #[post]
+//! pub fn login(user: String, token: String) -> Result<(), String> {
+//!     let key = format!("session:{}", token);
+//!     ttl::put_with_ttl(key, user, Duration::from_secs(3600)).map_err(|e| e.to_string())
+//! }
+//!
+//! #[get]
+//! pub fn session(token: String) -> Result<Option<Vec<u8>>, String> {
+//!     ttl::get(format!("session:{}", token)).map_err(|e| e.to_string())
+//! }
+//! ```
+//!
+//! With the `mock` feature:
+//!
+//! ```
+//! # #[cfg(feature = "mock")]
+//! # {
+//! use std::time::Duration;
+//! use vrs_core_sdk::{mock, storage, storage::ttl};
+//!
+//! mock::reset();
+//! ttl::put_with_ttl(b"session", b"alice", Duration::from_secs(1)).unwrap();
+//! ttl::put_with_ttl(b"token", b"bob", Duration::from_secs(1)).unwrap();
+//! assert_eq!(ttl::get(b"session").unwrap(), Some(b"alice".to_vec()));
+//! // overwritten without a TTL, so it no longer expires
+//! storage::put(b"token", b"carol").unwrap();
+//!
+//! mock::set_now(1000);
+//! assert_eq!(ttl::get(b"session").unwrap(), None);
+//! assert_eq!(ttl::get(b"token").unwrap(), Some(b"carol".to_vec()));
+//! assert_eq!(ttl::expires_at(b"token").unwrap(), None);
+//! assert_eq!(ttl::purge_expired(10).unwrap(), 2);
+//! assert_eq!(storage::get(b"session").unwrap(), None);
+//! assert_eq!(storage::get(b"token").unwrap(), Some(b"carol".to_vec()));
+//! assert!(mock::entries().iter().all(|(k, _)| !k.starts_with(b"__vrs:ttl:")));
+//! # }
+//! ```
+
+use super::{decode_value, del, get_range, Direction};
+use crate::{timer, CallResult};
+use codec::{Decode, Encode};
+use std::time::Duration;
+
+const EXPIRY_PREFIX: &[u8] = b"__vrs:ttl:k:";
+const INDEX_PREFIX: &[u8] = b"__vrs:ttl:e:";
+
+/// The expiry of a key, which only applies while the key holds the value it was set for.
+#[derive(Encode, Decode)]
+struct Expiry {
+    expires_at: u64,
+    value_hash: [u8; 16],
+}
+
+impl Expiry {
+    fn applies_to(&self, value: &[u8]) -> bool {
+        self.value_hash == sp_crypto_hashing::blake2_128(value)
+    }
+}
+
+fn expiry_key(key: &[u8]) -> Vec<u8> {
+    [EXPIRY_PREFIX, key].concat()
+}
+
+fn index_key(expires_at: u64, key: &[u8]) -> Vec<u8> {
+    [INDEX_PREFIX, &expires_at.to_be_bytes()[..], key].concat()
+}
+
+fn expiry(key: &[u8]) -> CallResult<Option<Expiry>> {
+    let expiry_key = expiry_key(key);
+    super::get(&expiry_key)?
+        .map(|v| decode_value(&expiry_key, &v))
+        .transpose()
+}
+
+/// Put a key-value pair which expires after `ttl`, replacing the previous expiry of the key.
+pub fn put_with_ttl(
+    key: impl AsRef<[u8]>,
+    value: impl AsRef<[u8]>,
+    ttl: Duration,
+) -> CallResult<()> {
+    let key = key.as_ref();
+    let value = value.as_ref();
+    let expires_at = timer::now().saturating_add(ttl.as_millis() as u64);
+    if let Some(old) = expiry(key)? {
+        del(index_key(old.expires_at, key))?;
+    }
+    super::put(key, value)?;
+    let expiry = Expiry {
+        expires_at,
+        value_hash: sp_crypto_hashing::blake2_128(value),
+    };
+    super::put(expiry_key(key), expiry.encode())?;
+    super::put(index_key(expires_at, key), [])
+}
+
+/// Get the value of `key`, `None` if the key is absent or has expired.
+pub fn get(key: impl AsRef<[u8]>) -> CallResult<Option<Vec<u8>>> {
+    let key = key.as_ref();
+    let Some(value) = super::get(key)? else {
+        return Ok(None);
+    };
+    match expiry(key)? {
+        Some(expiry) if expiry.expires_at <= timer::now() && expiry.applies_to(&value) => Ok(None),
+        _ => Ok(Some(value)),
+    }
+}
+
+/// Get the expiry time of `key`, `None` if the key has no TTL or was overwritten since.
+pub fn expires_at(key: impl AsRef<[u8]>) -> CallResult<Option<u64>> {
+    let key = key.as_ref();
+    let Some(value) = super::get(key)? else {
+        return Ok(None);
+    };
+    Ok(expiry(key)?
+        .filter(|expiry| expiry.applies_to(&value))
+        .map(|expiry| expiry.expires_at))
+}
+
+/// Remove the TTL of `key`, keeping its value.
+pub fn persist(key: impl AsRef<[u8]>) -> CallResult<()> {
+    let key = key.as_ref();
+    if let Some(expiry) = expiry(key)? {
+        del(index_key(expiry.expires_at, key))?;
+        del(expiry_key(key))?;
+    }
+    Ok(())
+}
+
+/// Remove `key` with its TTL.
+pub fn remove(key: impl AsRef<[u8]>) -> CallResult<()> {
+    let key = key.as_ref();
+    persist(key)?;
+    del(key)
+}
+
+/// Clear at most `limit` expired TTLs, the earliest first, and return how many were cleared.
+/// The key is removed along with its TTL unless it was overwritten since. The limit maximum is
+/// `MAX_RANGE_LIMIT`.
+pub fn purge_expired(limit: usize) -> CallResult<u32> {
+    let now = timer::now();
+    let mut cleared = 0;
+    for (index_key, _) in get_range(INDEX_PREFIX, Direction::Forward, limit)? {
+        let Some(entry) = index_key
+            .strip_prefix(INDEX_PREFIX)
+            .filter(|entry| entry.len() >= 8)
+        else {
+            break;
+        };
+        let (time, key) = entry.split_at(8);
+        let mut expires_at = [0u8; 8];
+        expires_at.copy_from_slice(time);
+        let expires_at = u64::from_be_bytes(expires_at);
+        if expires_at > now {
+            break;
+        }
+        if let Some(expiry) = expiry(key)?.filter(|expiry| expiry.expires_at == expires_at) {
+            if super::get(key)?.is_some_and(|value| expiry.applies_to(&value)) {
+                del(key)?;
+            }
+            del(expiry_key(key))?;
+        }
+        del(&index_key)?;
+        cleared += 1;
+    }
+    Ok(cleared)
+}
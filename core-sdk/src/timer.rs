@@ -42,6 +42,7 @@ pub fn _set_timer(ts: Duration, func: &[u8], params: &[u8]) -> Result<(), Runtim
             "func size exceeds maximum allowed size".to_string(),
         ));
     }
+    // the bounds above keep the delay and the lengths within `i32`
     let status = unsafe {
         timer_set_delay(
             ts.as_secs() as i32,
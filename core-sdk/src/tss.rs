@@ -42,6 +42,30 @@ impl TryFrom<u8> for CryptoType {
     }
 }
 
+#[cfg(not(feature = "legacy-host"))]
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn tss_get_public_key_host_fn_v2(
+        crypto_type: u8,
+        tweak_ptr: *const u8,
+        tweak_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+    fn tss_sign_host_fn_v2(
+        crypto_type: u8,
+        tweak_ptr: *const u8,
+        tweak_len: i32,
+        message_ptr: *const u8,
+        message_len: i32,
+        return_ptr: *mut u8,
+        return_cap: i32,
+        offset: i32,
+    ) -> i32;
+}
+
+#[cfg(feature = "legacy-host")]
 #[link(wasm_import_module = "env")]
 extern "C" {
     fn tss_get_public_key_host_fn(
@@ -63,18 +87,23 @@ extern "C" {
 /// get the public key of the given crypto type with the tweak
 pub fn tss_get_public_key(crypto_type: CryptoType, tweak: impl AsRef<[u8]>) -> CallResult<Vec<u8>> {
     let tweak = tweak.as_ref();
-    let mut buf = crate::allocate_buffer();
-    let status = unsafe {
-        tss_get_public_key_host_fn(
+    let tweak_len = crate::check_len(tweak, RuntimeError::ValueTooLarge)?;
+    #[cfg(not(feature = "legacy-host"))]
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        tss_get_public_key_host_fn_v2(
             crypto_type.into(),
             tweak.as_ptr(),
-            tweak.len() as i32,
-            buf.as_mut_ptr(),
+            tweak_len,
+            ptr,
+            cap,
+            offset,
         )
-    };
-    assert!(status == crate::NO_MORE_DATA);
-    CallResult::<Vec<u8>>::decode(&mut &buf[..])
-        .map_err(|_| RuntimeError::DecodeReturnValueError)?
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let buf = crate::read_legacy_buffer(|ptr| unsafe {
+        tss_get_public_key_host_fn(crypto_type.into(), tweak.as_ptr(), tweak_len, ptr)
+    })?;
+    crate::decode_host_result(&buf)
 }
 
 /// sign the message with the given crypto type and tweak
@@ -85,18 +114,31 @@ pub fn tss_sign(
 ) -> CallResult<Vec<u8>> {
     let tweak = tweak.as_ref();
     let message = message.as_ref();
-    let mut buf = crate::allocate_buffer();
-    let status = unsafe {
+    let tweak_len = crate::check_len(tweak, RuntimeError::ValueTooLarge)?;
+    let message_len = crate::check_len(message, RuntimeError::ValueTooLarge)?;
+    #[cfg(not(feature = "legacy-host"))]
+    let buf = crate::read_host_buffer(|ptr, cap, offset| unsafe {
+        tss_sign_host_fn_v2(
+            crypto_type.into(),
+            tweak.as_ptr(),
+            tweak_len,
+            message.as_ptr(),
+            message_len,
+            ptr,
+            cap,
+            offset,
+        )
+    })?;
+    #[cfg(feature = "legacy-host")]
+    let buf = crate::read_legacy_buffer(|ptr| unsafe {
         tss_sign_host_fn(
             crypto_type.into(),
             tweak.as_ptr(),
-            tweak.len() as i32,
+            tweak_len,
             message.as_ptr(),
-            message.len() as i32,
-            buf.as_mut_ptr(),
+            message_len,
+            ptr,
         )
-    };
-    assert!(status == crate::NO_MORE_DATA);
-    CallResult::<Vec<u8>>::decode(&mut &buf[..])
-        .map_err(|_| RuntimeError::DecodeReturnValueError)?
+    })?;
+    crate::decode_host_result(&buf)
 }